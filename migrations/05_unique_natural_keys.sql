@@ -0,0 +1,39 @@
+-- Drop the duplicates earlier non-idempotent runs appended, keeping the newest row
+DELETE FROM Rune_Pool_Data_Meta a USING Rune_Pool_Data_Meta b
+    WHERE a.id < b.id AND a.startTime = b.startTime AND a.endTime = b.endTime;
+DELETE FROM Rune_Pool_Data_Intervals a USING Rune_Pool_Data_Intervals b
+    WHERE a.id < b.id AND a.startTime = b.startTime AND a.endTime = b.endTime;
+DELETE FROM Rune_Pool_Depth_Meta a USING Rune_Pool_Depth_Meta b
+    WHERE a.id < b.id AND a.startTime = b.startTime AND a.endTime = b.endTime;
+DELETE FROM Rune_Pool_Depth_Intervals a USING Rune_Pool_Depth_Intervals b
+    WHERE a.id < b.id AND a.startTime = b.startTime AND a.endTime = b.endTime;
+DELETE FROM earning_data_rune_pool_meta a USING earning_data_rune_pool_meta b
+    WHERE a.id < b.id AND a.start_time = b.start_time AND a.end_time = b.end_time;
+-- cascades to the pool rows of the dropped intervals
+DELETE FROM earning_data_rune_pool_interval a USING earning_data_rune_pool_interval b
+    WHERE a.id < b.id AND a.start_time = b.start_time AND a.end_time = b.end_time;
+DELETE FROM earning_data_pool_data a USING earning_data_pool_data b
+    WHERE a.id < b.id AND a.interval_id = b.interval_id AND a.pool = b.pool;
+DELETE FROM swap_data_rune_pool_meta a USING swap_data_rune_pool_meta b
+    WHERE a.id < b.id AND a.start_time = b.start_time AND a.end_time = b.end_time;
+DELETE FROM swap_data_rune_pool_interval a USING swap_data_rune_pool_interval b
+    WHERE a.id < b.id AND a.start_time = b.start_time AND a.end_time = b.end_time;
+
+ALTER TABLE Rune_Pool_Data_Meta
+    ADD CONSTRAINT rune_pool_data_meta_window_key UNIQUE (startTime, endTime);
+ALTER TABLE Rune_Pool_Data_Intervals
+    ADD CONSTRAINT rune_pool_data_intervals_window_key UNIQUE (startTime, endTime);
+ALTER TABLE Rune_Pool_Depth_Meta
+    ADD CONSTRAINT rune_pool_depth_meta_window_key UNIQUE (startTime, endTime);
+ALTER TABLE Rune_Pool_Depth_Intervals
+    ADD CONSTRAINT rune_pool_depth_intervals_window_key UNIQUE (startTime, endTime);
+ALTER TABLE earning_data_rune_pool_meta
+    ADD CONSTRAINT earning_data_rune_pool_meta_window_key UNIQUE (start_time, end_time);
+ALTER TABLE earning_data_rune_pool_interval
+    ADD CONSTRAINT earning_data_rune_pool_interval_window_key UNIQUE (start_time, end_time);
+ALTER TABLE earning_data_pool_data
+    ADD CONSTRAINT earning_data_pool_data_interval_pool_key UNIQUE (interval_id, pool);
+ALTER TABLE swap_data_rune_pool_meta
+    ADD CONSTRAINT swap_data_rune_pool_meta_window_key UNIQUE (start_time, end_time);
+ALTER TABLE swap_data_rune_pool_interval
+    ADD CONSTRAINT swap_data_rune_pool_interval_window_key UNIQUE (start_time, end_time);
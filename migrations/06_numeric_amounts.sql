@@ -0,0 +1,101 @@
+-- Midgard amounts are u128 strings; NUMERIC(39,0) holds every u128 without loss
+-- (earnings pool rewards can be negative, which NUMERIC also covers)
+
+ALTER TABLE Rune_Pool_Data_Meta
+    ALTER COLUMN startUnits TYPE NUMERIC(39, 0),
+    ALTER COLUMN startCount TYPE NUMERIC(39, 0),
+    ALTER COLUMN endUnits TYPE NUMERIC(39, 0),
+    ALTER COLUMN endCount TYPE NUMERIC(39, 0);
+
+ALTER TABLE Rune_Pool_Data_Intervals
+    ALTER COLUMN count TYPE NUMERIC(39, 0),
+    ALTER COLUMN units TYPE NUMERIC(39, 0);
+
+ALTER TABLE Rune_Pool_Depth_Meta
+    ALTER COLUMN startAssetDepth TYPE NUMERIC(39, 0),
+    ALTER COLUMN startRuneDepth TYPE NUMERIC(39, 0),
+    ALTER COLUMN startLPUnits TYPE NUMERIC(39, 0),
+    ALTER COLUMN startMemberCount TYPE NUMERIC(39, 0),
+    ALTER COLUMN startSynthUnits TYPE NUMERIC(39, 0),
+    ALTER COLUMN endAssetDepth TYPE NUMERIC(39, 0),
+    ALTER COLUMN endRuneDepth TYPE NUMERIC(39, 0),
+    ALTER COLUMN endLPUnits TYPE NUMERIC(39, 0),
+    ALTER COLUMN endMemberCount TYPE NUMERIC(39, 0),
+    ALTER COLUMN endSynthUnits TYPE NUMERIC(39, 0);
+
+ALTER TABLE Rune_Pool_Depth_Intervals
+    ALTER COLUMN assetDepth TYPE NUMERIC(39, 0),
+    ALTER COLUMN runeDepth TYPE NUMERIC(39, 0),
+    ALTER COLUMN liquidityUnits TYPE NUMERIC(39, 0),
+    ALTER COLUMN membersCount TYPE NUMERIC(39, 0),
+    ALTER COLUMN synthUnits TYPE NUMERIC(39, 0),
+    ALTER COLUMN synthSupply TYPE NUMERIC(39, 0),
+    ALTER COLUMN units TYPE NUMERIC(39, 0);
+
+ALTER TABLE earning_data_rune_pool_interval
+    ALTER COLUMN liquidity_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN block_rewards TYPE NUMERIC(39, 0),
+    ALTER COLUMN earnings TYPE NUMERIC(39, 0),
+    ALTER COLUMN bonding_earnings TYPE NUMERIC(39, 0),
+    ALTER COLUMN liquidity_earnings TYPE NUMERIC(39, 0);
+
+ALTER TABLE earning_data_pool_data
+    ALTER COLUMN asset_liquidity_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN rune_liquidity_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN total_liquidity_fees_rune TYPE NUMERIC(39, 0),
+    ALTER COLUMN saver_earning TYPE NUMERIC(39, 0),
+    ALTER COLUMN rewards TYPE NUMERIC(39, 0),
+    ALTER COLUMN earnings TYPE NUMERIC(39, 0);
+
+ALTER TABLE earning_data_rune_pool_meta
+    ALTER COLUMN liquidity_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN block_rewards TYPE NUMERIC(39, 0),
+    ALTER COLUMN earnings TYPE NUMERIC(39, 0),
+    ALTER COLUMN bonding_earnings TYPE NUMERIC(39, 0),
+    ALTER COLUMN liquidity_earnings TYPE NUMERIC(39, 0);
+
+ALTER TABLE swap_data_rune_pool_meta
+    ALTER COLUMN to_asset_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_rune_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_trade_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN from_trade_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_mint_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_redeem_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN total_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_asset_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_rune_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_trade_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN from_trade_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_mint_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_redeem_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN total_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_asset_volume_usd TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_rune_volume_usd TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_trade_volume_usd TYPE NUMERIC(39, 0),
+    ALTER COLUMN from_trade_volume_usd TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_mint_volume_usd TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_redeem_volume_usd TYPE NUMERIC(39, 0),
+    ALTER COLUMN total_volume_usd TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_asset_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_rune_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_trade_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN from_trade_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_mint_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_redeem_fees TYPE NUMERIC(39, 0),
+    ALTER COLUMN total_fees TYPE NUMERIC(39, 0);
+
+ALTER TABLE swap_data_rune_pool_interval
+    ALTER COLUMN to_asset_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_rune_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_trade_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN from_trade_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_mint_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_redeem_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN total_count TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_asset_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_rune_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN to_trade_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN from_trade_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_mint_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN synth_redeem_volume TYPE NUMERIC(39, 0),
+    ALTER COLUMN total_volume TYPE NUMERIC(39, 0);
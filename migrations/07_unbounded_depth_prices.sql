@@ -0,0 +1,9 @@
+-- NUMERIC(10, 8) caps values below 100, too small for BTC.BTC or ETH.ETH prices
+ALTER TABLE Rune_Pool_Depth_Meta
+    ALTER COLUMN priceShiftLoss TYPE NUMERIC,
+    ALTER COLUMN luviIncrease TYPE NUMERIC;
+
+ALTER TABLE Rune_Pool_Depth_Intervals
+    ALTER COLUMN assetPrice TYPE NUMERIC,
+    ALTER COLUMN assetPriceUSD TYPE NUMERIC,
+    ALTER COLUMN luvi TYPE NUMERIC;
@@ -0,0 +1,12 @@
+-- Depths were only ever fetched for AVAX.AVAX before pools were tracked
+ALTER TABLE Rune_Pool_Depth_Meta ADD COLUMN pool TEXT NOT NULL DEFAULT 'AVAX.AVAX';
+ALTER TABLE Rune_Pool_Depth_Meta ALTER COLUMN pool DROP DEFAULT;
+ALTER TABLE Rune_Pool_Depth_Intervals ADD COLUMN pool TEXT NOT NULL DEFAULT 'AVAX.AVAX';
+ALTER TABLE Rune_Pool_Depth_Intervals ALTER COLUMN pool DROP DEFAULT;
+
+ALTER TABLE Rune_Pool_Depth_Meta DROP CONSTRAINT rune_pool_depth_meta_window_key;
+ALTER TABLE Rune_Pool_Depth_Meta
+    ADD CONSTRAINT rune_pool_depth_meta_pool_window_key UNIQUE (pool, startTime, endTime);
+ALTER TABLE Rune_Pool_Depth_Intervals DROP CONSTRAINT rune_pool_depth_intervals_window_key;
+ALTER TABLE Rune_Pool_Depth_Intervals
+    ADD CONSTRAINT rune_pool_depth_intervals_pool_window_key UNIQUE (pool, startTime, endTime);
@@ -0,0 +1,13 @@
+-- Swap history is kept per pool alongside Midgard's all-pools series, which
+-- is stored under the 'ALL' pool key (every row so far came from it)
+ALTER TABLE swap_data_rune_pool_meta ADD COLUMN pool TEXT NOT NULL DEFAULT 'ALL';
+ALTER TABLE swap_data_rune_pool_meta ALTER COLUMN pool DROP DEFAULT;
+ALTER TABLE swap_data_rune_pool_interval ADD COLUMN pool TEXT NOT NULL DEFAULT 'ALL';
+ALTER TABLE swap_data_rune_pool_interval ALTER COLUMN pool DROP DEFAULT;
+
+ALTER TABLE swap_data_rune_pool_meta DROP CONSTRAINT swap_data_rune_pool_meta_window_key;
+ALTER TABLE swap_data_rune_pool_meta
+    ADD CONSTRAINT swap_data_rune_pool_meta_pool_window_key UNIQUE (pool, start_time, end_time);
+ALTER TABLE swap_data_rune_pool_interval DROP CONSTRAINT swap_data_rune_pool_interval_window_key;
+ALTER TABLE swap_data_rune_pool_interval
+    ADD CONSTRAINT swap_data_rune_pool_interval_pool_window_key UNIQUE (pool, start_time, end_time);
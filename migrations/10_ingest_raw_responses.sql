@@ -0,0 +1,25 @@
+-- Every Midgard payload the pipeline stored, gzipped exactly as received, so
+-- a suspicious number can be traced back to what Midgard actually returned
+CREATE TABLE IF NOT EXISTS ingest_raw_responses (
+    id BIGSERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    fetched_at TIMESTAMPTZ NOT NULL,
+    http_status SMALLINT NOT NULL,
+    -- hex SHA-256 of the uncompressed body
+    sha256 TEXT NOT NULL,
+    body_size INTEGER NOT NULL,
+    body_gzip BYTEA NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS ingest_raw_responses_sha256_idx ON ingest_raw_responses (sha256);
+
+-- The response each interval row was last written from; NULL for rows
+-- ingested before archival existed
+ALTER TABLE Rune_Pool_Data_Intervals
+    ADD COLUMN raw_response_id BIGINT REFERENCES ingest_raw_responses (id);
+ALTER TABLE Rune_Pool_Depth_Intervals
+    ADD COLUMN raw_response_id BIGINT REFERENCES ingest_raw_responses (id);
+ALTER TABLE earning_data_rune_pool_interval
+    ADD COLUMN raw_response_id BIGINT REFERENCES ingest_raw_responses (id);
+ALTER TABLE swap_data_rune_pool_interval
+    ADD COLUMN raw_response_id BIGINT REFERENCES ingest_raw_responses (id);
@@ -0,0 +1,24 @@
+-- One row per scheduled poll, backfill or gap repair of a series, written
+-- when it starts and completed when it finishes, so stalled or failing
+-- ingestion is visible
+CREATE TABLE IF NOT EXISTS ingestion_runs (
+    id BIGSERIAL PRIMARY KEY,
+    dataset TEXT NOT NULL,
+    -- NULL for the pool-less series (runepool, earnings, all-pools swaps)
+    pool TEXT,
+    kind TEXT NOT NULL CHECK (kind IN ('poll', 'backfill', 'repair')),
+    requested_from BIGINT,
+    requested_to BIGINT,
+    -- 'running', 'succeeded' or 'failed'
+    status TEXT NOT NULL DEFAULT 'running',
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    finished_at TIMESTAMPTZ,
+    duration_ms BIGINT,
+    rows_inserted BIGINT,
+    rows_updated BIGINT,
+    rows_skipped BIGINT,
+    error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS ingestion_runs_started_at_idx ON ingestion_runs (started_at DESC);
+CREATE INDEX IF NOT EXISTS ingestion_runs_series_idx ON ingestion_runs (dataset, pool, started_at DESC);
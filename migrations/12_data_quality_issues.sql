@@ -0,0 +1,20 @@
+-- Invariants between a Midgard response's meta and its intervals that did
+-- not hold at ingest time, e.g. a swap meta totalCount that is not the sum
+-- of the intervals' totalCount. Values are u128s, hence NUMERIC(39, 0).
+CREATE TABLE IF NOT EXISTS data_quality_issues (
+    id BIGSERIAL PRIMARY KEY,
+    dataset TEXT NOT NULL,
+    -- NULL for the pool-less series (runepool, earnings, all-pools swaps)
+    pool TEXT,
+    raw_response_id BIGINT REFERENCES ingest_raw_responses(id),
+    check_name TEXT NOT NULL,
+    -- derived from the intervals
+    expected NUMERIC(39, 0) NOT NULL,
+    -- what meta claims
+    actual NUMERIC(39, 0) NOT NULL,
+    -- whether the payload's intervals were kept out of the tables
+    rejected BOOLEAN NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS data_quality_issues_series_idx ON data_quality_issues (dataset, pool, detected_at DESC);
@@ -0,0 +1,22 @@
+-- Midgard's swap intervals carry the same USD volumes, fees and per-kind
+-- slips as its meta, but only the meta kept them. Rows stored before this
+-- read 0 for them until their window is fetched again.
+ALTER TABLE swap_data_rune_pool_interval
+    ADD COLUMN to_asset_volume_usd NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN to_rune_volume_usd NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN to_trade_volume_usd NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN from_trade_volume_usd NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN synth_mint_volume_usd NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN synth_redeem_volume_usd NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN total_volume_usd NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN to_asset_fees NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN to_rune_fees NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN to_trade_fees NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN from_trade_fees NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN synth_mint_fees NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN synth_redeem_fees NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN total_fees NUMERIC(39, 0) NOT NULL DEFAULT 0,
+    ADD COLUMN to_trade_average_slip FLOAT NOT NULL DEFAULT 0,
+    ADD COLUMN from_trade_average_slip FLOAT NOT NULL DEFAULT 0,
+    ADD COLUMN synth_mint_average_slip FLOAT NOT NULL DEFAULT 0,
+    ADD COLUMN synth_redeem_average_slip FLOAT NOT NULL DEFAULT 0;
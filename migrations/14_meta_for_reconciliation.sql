@@ -0,0 +1,11 @@
+-- The API computes meta from the stored intervals for whatever window it is
+-- asked about. The meta Midgard sent with each ingested page is still stored,
+-- but only to reconcile against.
+COMMENT ON TABLE Rune_Pool_Data_Meta IS
+    'Midgard''s meta per ingested page, kept for reconciliation; the API computes meta from the intervals';
+COMMENT ON TABLE Rune_Pool_Depth_Meta IS
+    'Midgard''s meta per ingested page, kept for reconciliation; the API computes meta from the intervals';
+COMMENT ON TABLE earning_data_rune_pool_meta IS
+    'Midgard''s meta per ingested page, kept for reconciliation; the API computes meta from the intervals';
+COMMENT ON TABLE swap_data_rune_pool_meta IS
+    'Midgard''s meta per ingested page, kept for reconciliation; the API computes meta from the intervals';
@@ -0,0 +1,64 @@
+-- The zeros migration 13 filled in for rows stored before it are not
+-- Midgard's values. The columns take NULL for "not known" instead, which the
+-- API serves as Midgard's "NaN", and the rows that carry made-up zeros get it:
+-- they are the ones with volume but no USD volume. Fetching their window
+-- again fills them in.
+ALTER TABLE swap_data_rune_pool_interval
+    ALTER COLUMN to_asset_volume_usd DROP NOT NULL,
+    ALTER COLUMN to_asset_volume_usd DROP DEFAULT,
+    ALTER COLUMN to_rune_volume_usd DROP NOT NULL,
+    ALTER COLUMN to_rune_volume_usd DROP DEFAULT,
+    ALTER COLUMN to_trade_volume_usd DROP NOT NULL,
+    ALTER COLUMN to_trade_volume_usd DROP DEFAULT,
+    ALTER COLUMN from_trade_volume_usd DROP NOT NULL,
+    ALTER COLUMN from_trade_volume_usd DROP DEFAULT,
+    ALTER COLUMN synth_mint_volume_usd DROP NOT NULL,
+    ALTER COLUMN synth_mint_volume_usd DROP DEFAULT,
+    ALTER COLUMN synth_redeem_volume_usd DROP NOT NULL,
+    ALTER COLUMN synth_redeem_volume_usd DROP DEFAULT,
+    ALTER COLUMN total_volume_usd DROP NOT NULL,
+    ALTER COLUMN total_volume_usd DROP DEFAULT,
+    ALTER COLUMN to_asset_fees DROP NOT NULL,
+    ALTER COLUMN to_asset_fees DROP DEFAULT,
+    ALTER COLUMN to_rune_fees DROP NOT NULL,
+    ALTER COLUMN to_rune_fees DROP DEFAULT,
+    ALTER COLUMN to_trade_fees DROP NOT NULL,
+    ALTER COLUMN to_trade_fees DROP DEFAULT,
+    ALTER COLUMN from_trade_fees DROP NOT NULL,
+    ALTER COLUMN from_trade_fees DROP DEFAULT,
+    ALTER COLUMN synth_mint_fees DROP NOT NULL,
+    ALTER COLUMN synth_mint_fees DROP DEFAULT,
+    ALTER COLUMN synth_redeem_fees DROP NOT NULL,
+    ALTER COLUMN synth_redeem_fees DROP DEFAULT,
+    ALTER COLUMN total_fees DROP NOT NULL,
+    ALTER COLUMN total_fees DROP DEFAULT,
+    ALTER COLUMN to_trade_average_slip DROP NOT NULL,
+    ALTER COLUMN to_trade_average_slip DROP DEFAULT,
+    ALTER COLUMN from_trade_average_slip DROP NOT NULL,
+    ALTER COLUMN from_trade_average_slip DROP DEFAULT,
+    ALTER COLUMN synth_mint_average_slip DROP NOT NULL,
+    ALTER COLUMN synth_mint_average_slip DROP DEFAULT,
+    ALTER COLUMN synth_redeem_average_slip DROP NOT NULL,
+    ALTER COLUMN synth_redeem_average_slip DROP DEFAULT;
+
+UPDATE swap_data_rune_pool_interval
+SET
+    to_asset_volume_usd = NULL,
+    to_rune_volume_usd = NULL,
+    to_trade_volume_usd = NULL,
+    from_trade_volume_usd = NULL,
+    synth_mint_volume_usd = NULL,
+    synth_redeem_volume_usd = NULL,
+    total_volume_usd = NULL,
+    to_asset_fees = NULL,
+    to_rune_fees = NULL,
+    to_trade_fees = NULL,
+    from_trade_fees = NULL,
+    synth_mint_fees = NULL,
+    synth_redeem_fees = NULL,
+    total_fees = NULL,
+    to_trade_average_slip = NULL,
+    from_trade_average_slip = NULL,
+    synth_mint_average_slip = NULL,
+    synth_redeem_average_slip = NULL
+WHERE total_volume > 0 AND total_volume_usd = 0;
@@ -0,0 +1,234 @@
+use crate::config::{parse_unix_or_date, Config};
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::midgard_client::MidgardClient;
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::{backfill, gaps, ingest, scheduler};
+use crate::mock_midgard::server::MockOptions;
+use crate::{migration_script, start_server, verify};
+use clap::{Parser, Subcommand};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+
+// Process exit codes, stable so cron jobs and orchestrators can act on them
+pub const EXIT_OK: u8 = 0;
+// a step failed, e.g. a Midgard payload could not be stored
+pub const EXIT_FAILURE: u8 = 1;
+// bad arguments; clap uses the same code for its own parse errors
+pub const EXIT_USAGE: u8 = 2;
+// `verify` ran and found inconsistencies
+pub const EXIT_INCONSISTENT: u8 = 3;
+// Midgard or Postgres could not be reached, retrying later may succeed
+pub const EXIT_UNAVAILABLE: u8 = 69;
+// the configuration file or environment is invalid
+pub const EXIT_CONFIG: u8 = 78;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Mirrors Midgard history into Postgres and serves it over HTTP"
+)]
+pub struct Cli {
+    /// Without a subcommand: migrate, backfill if configured, then ingest and serve
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Apply pending database migrations and exit
+    Migrate,
+    /// Fetch the intervals newer than what is stored once, then exit
+    Ingest {
+        /// Dataset to ingest, repeatable; defaults to every enabled dataset
+        #[arg(long = "dataset", value_enum)]
+        datasets: Vec<Dataset>,
+        /// Only this pool for the per-pool datasets (depths, swaps)
+        #[arg(long)]
+        pool: Option<String>,
+    },
+    /// Backfill history between two points in time, then exit
+    Backfill {
+        /// Unix seconds or YYYY-MM-DD (UTC)
+        #[arg(long, value_parser = parse_unix_or_date)]
+        from: i64,
+        /// Unix seconds or YYYY-MM-DD (UTC); defaults to now
+        #[arg(long, value_parser = parse_unix_or_date)]
+        to: Option<i64>,
+        /// Dataset to backfill, repeatable; defaults to every enabled dataset
+        #[arg(long = "dataset", value_enum)]
+        datasets: Vec<Dataset>,
+    },
+    /// Refetch the intervals missing between stored ones, then exit
+    Repair {
+        /// Dataset to repair, repeatable; defaults to every enabled dataset
+        #[arg(long = "dataset", value_enum)]
+        datasets: Vec<Dataset>,
+    },
+    /// Serve the API only, without migrating or ingesting
+    Serve,
+    /// Check the stored data for inconsistencies; exits 3 if any are found
+    Verify,
+    /// Serve deterministic synthetic Midgard history for end-to-end testing
+    MockMidgard(MockOptions),
+}
+
+// The datasets a command should cover: the ones asked for, or all enabled
+fn selected(config: &Config, datasets: Vec<Dataset>) -> Vec<Dataset> {
+    if datasets.is_empty() {
+        Dataset::ALL
+            .into_iter()
+            .filter(|dataset| config.ingestion.schedule(*dataset).enabled)
+            .collect()
+    } else {
+        datasets
+    }
+}
+
+async fn series_for(
+    midgard: &MidgardClient,
+    config: &Config,
+    dataset: Dataset,
+    pool: Option<&str>,
+) -> Result<Vec<Option<String>>, AppError> {
+    match pool {
+        Some(pool) if dataset.is_per_pool() => Ok(vec![Some(pool.to_string())]),
+        _ => ingest::pools_to_ingest(midgard, dataset, &config.ingestion.pools).await,
+    }
+}
+
+async fn run_backfill(
+    midgard: &MidgardClient,
+    db: &PgPool,
+    config: &Config,
+    datasets: Vec<Dataset>,
+    from: i64,
+    to: Option<i64>,
+) -> Result<(), AppError> {
+    for dataset in datasets {
+        let schedule = config.ingestion.schedule(dataset);
+        for pool in series_for(midgard, config, dataset, None).await? {
+            let rows = backfill::backfill(
+                midgard,
+                db,
+                dataset,
+                pool.as_deref(),
+                schedule,
+                from,
+                to,
+                scheduler::unix_now(),
+            )
+            .await?;
+            std::println!(
+                "Backfilled {} {} intervals",
+                rows.stored(),
+                dataset.series_label(pool.as_deref())
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn run(cli: Cli, config: Config) -> Result<u8, AppError> {
+    let db = PgPoolOptions::new()
+        .max_connections(config.database.max_connections)
+        .connect(config.database.url.as_deref().unwrap_or_default())
+        .await?;
+    let midgard = MidgardClient::from_config(&config.midgard);
+
+    match cli.command {
+        Some(Command::Migrate) => migration_script(&db).await?,
+        Some(Command::Ingest { datasets, pool }) => {
+            // keep going past a failed series, but report the last failure
+            let mut failure = None;
+            for dataset in selected(&config, datasets) {
+                let schedule = config.ingestion.schedule(dataset);
+                for series in series_for(&midgard, &config, dataset, pool.as_deref()).await? {
+                    match scheduler::poll_once(
+                        &midgard,
+                        &db,
+                        dataset,
+                        series.as_deref(),
+                        schedule,
+                        scheduler::unix_now(),
+                    )
+                    .await
+                    {
+                        Ok(rows) => std::println!(
+                            "Ingested {} new {} intervals",
+                            rows.inserted,
+                            dataset.series_label(series.as_deref())
+                        ),
+                        Err(e) => {
+                            eprintln!(
+                                "Ingest of {} failed: {}",
+                                dataset.series_label(series.as_deref()),
+                                e
+                            );
+                            failure = Some(e);
+                        }
+                    }
+                }
+            }
+            if let Some(e) = failure {
+                return Err(e);
+            }
+        }
+        Some(Command::Backfill { from, to, datasets }) => {
+            if to.is_some_and(|to| to <= from) {
+                return Err(AppError::Validation(
+                    "--to must be after --from".to_string(),
+                ));
+            }
+            run_backfill(
+                &midgard,
+                &db,
+                &config,
+                selected(&config, datasets),
+                from,
+                to,
+            )
+            .await?;
+        }
+        Some(Command::Repair { datasets }) => {
+            for dataset in selected(&config, datasets) {
+                let schedule = config.ingestion.schedule(dataset);
+                let rows =
+                    gaps::repair_gaps(&midgard, &db, dataset, schedule, &config.ingestion.pools)
+                        .await?;
+                std::println!(
+                    "Repaired {} missing {} intervals",
+                    rows.inserted,
+                    dataset.as_str()
+                );
+            }
+        }
+        Some(Command::Serve) => start_server(db, config.server.addr()).await?,
+        Some(Command::Verify) => {
+            let checks = verify::verify(&db).await?;
+            for check in &checks {
+                if check.passed() {
+                    std::println!("ok      {}", check.name);
+                } else {
+                    std::println!("FAILED  {} ({} problems)", check.name, check.problems);
+                }
+            }
+            if !checks.iter().all(|check| check.passed()) {
+                return Ok(EXIT_INCONSISTENT);
+            }
+        }
+        // main runs the mock before any config or database is needed
+        Some(Command::MockMidgard(_)) => unreachable!("mock-midgard is handled by main"),
+        None => {
+            migration_script(&db).await?;
+            if let Some(from) = config.ingestion.backfill.from {
+                let datasets = selected(&config, Vec::new());
+                let to = config.ingestion.backfill.to;
+                run_backfill(&midgard, &db, &config, datasets, from, to).await?;
+            }
+            // keeps the tables current from here on, resuming from what is stored
+            scheduler::spawn_scheduler(midgard, db.clone(), &config.ingestion);
+            start_server(db, config.server.addr()).await?;
+        }
+    }
+    Ok(EXIT_OK)
+}
@@ -0,0 +1,548 @@
+use crate::fetch_data_from_midgard::fixtures::FixtureMode;
+use crate::fetch_data_from_midgard::midgard_client::DEFAULT_MIDGARD_BASE_URL;
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::validation::OnInconsistency;
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Deserializer};
+use std::env;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Read when present; `CONFIG_FILE` points somewhere else and must exist
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+// Pool statuses Midgard's `/v2/pools?status=` understands
+const POOL_STATUSES: [&str; 3] = ["available", "staged", "suspended"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("could not read config file {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid config file {path}: {source}")]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+    #[error("environment variable {name}={value:?} is invalid: {reason}")]
+    Env {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    #[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+// Settings from the config file, with environment variables taking precedence
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub midgard: MidgardConfig,
+    pub ingestion: IngestionConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8080,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+    pub max_connections: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: None,
+            max_connections: 10,
+        }
+    }
+}
+
+// Several base URLs are tried in turn when a request has to be retried
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MidgardConfig {
+    pub base_urls: Vec<String>,
+    pub timeout_secs: u64,
+    pub max_retries: u32,
+    pub requests_per_second: f64,
+    // `replay` reads responses from `fixture_dir` instead of Midgard,
+    // `record` fetches from Midgard and saves every response there
+    pub fixture_mode: FixtureMode,
+    pub fixture_dir: PathBuf,
+}
+
+impl Default for MidgardConfig {
+    fn default() -> Self {
+        MidgardConfig {
+            base_urls: vec![DEFAULT_MIDGARD_BASE_URL.to_string()],
+            timeout_secs: 30,
+            max_retries: 5,
+            requests_per_second: 5.0,
+            fixture_mode: FixtureMode::Live,
+            fixture_dir: PathBuf::from("fixtures"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IngestionConfig {
+    pub runepool: Schedule,
+    pub depths: Schedule,
+    pub earnings: Schedule,
+    pub swaps: Schedule,
+    pub pools: PoolSelection,
+    pub backfill: BackfillConfig,
+}
+
+impl IngestionConfig {
+    pub fn schedule(&self, dataset: Dataset) -> &Schedule {
+        match dataset {
+            Dataset::RunePool => &self.runepool,
+            Dataset::Depths => &self.depths,
+            Dataset::Earnings => &self.earnings,
+            Dataset::Swaps => &self.swaps,
+        }
+    }
+
+    fn schedule_mut(&mut self, dataset: Dataset) -> &mut Schedule {
+        match dataset {
+            Dataset::RunePool => &mut self.runepool,
+            Dataset::Depths => &mut self.depths,
+            Dataset::Earnings => &mut self.earnings,
+            Dataset::Swaps => &mut self.swaps,
+        }
+    }
+}
+
+// Whether a dataset is polled, at which Midgard bucket size, whether each
+// poll also refetches the gaps found in what is stored, and what happens to
+// payloads whose meta disagrees with their intervals. Every dataset is
+// ingested hourly (see INGEST_INTERVAL).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schedule {
+    pub enabled: bool,
+    pub repair_gaps: bool,
+    pub on_inconsistency: OnInconsistency,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            enabled: true,
+            repair_gaps: false,
+            on_inconsistency: OnInconsistency::Record,
+        }
+    }
+}
+
+// Which Midgard pools the per-pool datasets follow. An empty allowlist
+// admits every pool; the denylist always wins.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolSelection {
+    pub status: Option<String>,
+    pub allowlist: Vec<String>,
+    pub denylist: Vec<String>,
+}
+
+impl PoolSelection {
+    pub fn admits(&self, pool: &str) -> bool {
+        (self.allowlist.is_empty() || self.allowlist.iter().any(|p| p == pool))
+            && !self.denylist.iter().any(|p| p == pool)
+    }
+}
+
+// Backfill bounds, as unix seconds or `YYYY-MM-DD` (midnight UTC). Without
+// `from` no backfill runs; without `to` it ends now.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackfillConfig {
+    #[serde(deserialize_with = "unix_or_date")]
+    pub from: Option<i64>,
+    #[serde(deserialize_with = "unix_or_date")]
+    pub to: Option<i64>,
+}
+
+fn unix_or_date<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Unix(i64),
+        Date(String),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Unix(seconds) => Ok(Some(seconds)),
+        Raw::Date(date) => parse_unix_or_date(&date)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+pub fn parse_unix_or_date(value: &str) -> Result<i64, String> {
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Ok(seconds);
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| {
+        format!(
+            "{:?} is not unix seconds or a YYYY-MM-DD date: {}",
+            value, e
+        )
+    })?;
+    Ok(date.and_time(NaiveTime::MIN).and_utc().timestamp())
+}
+
+fn env_value(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn parse_env<T>(
+    name: &str,
+    value: String,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<T, ConfigError> {
+    parse(value.trim()).map_err(|reason| ConfigError::Env {
+        name: name.to_string(),
+        value,
+        reason,
+    })
+}
+
+// Overwrites `target` when the variable is set
+fn override_from_env<T>(name: &str, target: &mut T) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = env_value(name) {
+        *target = parse_env(name, value, |v| {
+            v.parse().map_err(|e: T::Err| e.to_string())
+        })?;
+    }
+    Ok(())
+}
+
+fn comma_list(value: &str) -> Result<Vec<String>, String> {
+    Ok(value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+impl Config {
+    // Reads the config file (if any), applies environment overrides and
+    // validates the result
+    pub fn load() -> Result<Config, ConfigError> {
+        let mut config = match env_value("CONFIG_FILE") {
+            Some(path) => Config::from_file(&path)?,
+            None if std::path::Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(DEFAULT_CONFIG_FILE)?
+            }
+            None => Config::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &str) -> Result<Config, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_string(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_string(),
+            source,
+        })
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        override_from_env("SERVER_BIND", &mut self.server.bind)?;
+        override_from_env("SERVER_PORT", &mut self.server.port)?;
+
+        if let Some(url) = env_value("DATABASE_URL") {
+            self.database.url = Some(url);
+        }
+        override_from_env(
+            "DATABASE_MAX_CONNECTIONS",
+            &mut self.database.max_connections,
+        )?;
+
+        if let Some(value) = env_value("MIDGARD_BASE_URL") {
+            self.midgard.base_urls = parse_env("MIDGARD_BASE_URL", value, comma_list)?;
+        }
+        override_from_env("MIDGARD_TIMEOUT_SECS", &mut self.midgard.timeout_secs)?;
+        override_from_env("MIDGARD_MAX_RETRIES", &mut self.midgard.max_retries)?;
+        override_from_env(
+            "MIDGARD_REQUESTS_PER_SECOND",
+            &mut self.midgard.requests_per_second,
+        )?;
+        override_from_env("MIDGARD_FIXTURE_MODE", &mut self.midgard.fixture_mode)?;
+        override_from_env("MIDGARD_FIXTURE_DIR", &mut self.midgard.fixture_dir)?;
+
+        // e.g. INGEST_DEPTHS_ENABLED=false, INGEST_SWAPS_REPAIR_GAPS=true
+        for dataset in Dataset::ALL {
+            let prefix = format!("INGEST_{}", dataset.as_str().to_uppercase());
+            let schedule = self.ingestion.schedule_mut(dataset);
+            override_from_env(&format!("{}_ENABLED", prefix), &mut schedule.enabled)?;
+            override_from_env(
+                &format!("{}_REPAIR_GAPS", prefix),
+                &mut schedule.repair_gaps,
+            )?;
+            override_from_env(
+                &format!("{}_ON_INCONSISTENCY", prefix),
+                &mut schedule.on_inconsistency,
+            )?;
+        }
+
+        let pools = &mut self.ingestion.pools;
+        if let Some(status) = env_value("MIDGARD_POOL_STATUS") {
+            pools.status = Some(status);
+        }
+        if let Some(value) = env_value("POOL_ALLOWLIST") {
+            pools.allowlist = parse_env("POOL_ALLOWLIST", value, comma_list)?;
+        }
+        if let Some(value) = env_value("POOL_DENYLIST") {
+            pools.denylist = parse_env("POOL_DENYLIST", value, comma_list)?;
+        }
+
+        let backfill = &mut self.ingestion.backfill;
+        if let Some(value) = env_value("BACKFILL_FROM") {
+            backfill.from = Some(parse_env("BACKFILL_FROM", value, parse_unix_or_date)?);
+        }
+        if let Some(value) = env_value("BACKFILL_TO") {
+            backfill.to = Some(parse_env("BACKFILL_TO", value, parse_unix_or_date)?);
+        }
+        Ok(())
+    }
+
+    // Collects every problem instead of stopping at the first one
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.server.port == 0 {
+            problems.push("server.port must be between 1 and 65535".to_string());
+        }
+        if self.database.url.is_none() {
+            problems.push("database.url (or DATABASE_URL) must be set".to_string());
+        }
+        if self.database.max_connections == 0 {
+            problems.push("database.max_connections must be at least 1".to_string());
+        }
+
+        if self.midgard.base_urls.is_empty() {
+            problems.push("midgard.base_urls needs at least one URL".to_string());
+        }
+        for url in &self.midgard.base_urls {
+            match reqwest::Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+                _ => problems.push(format!(
+                    "midgard.base_urls: {:?} is not an http(s) URL",
+                    url
+                )),
+            }
+        }
+        if self.midgard.timeout_secs == 0 {
+            problems.push("midgard.timeout_secs must be at least 1".to_string());
+        }
+        if !(self.midgard.requests_per_second.is_finite() && self.midgard.requests_per_second > 0.0)
+        {
+            problems.push("midgard.requests_per_second must be a positive number".to_string());
+        }
+        if self.midgard.fixture_mode == FixtureMode::Replay && !self.midgard.fixture_dir.is_dir() {
+            problems.push(format!(
+                "midgard.fixture_dir {:?} must be a directory to replay from",
+                self.midgard.fixture_dir
+            ));
+        }
+
+        let pools = &self.ingestion.pools;
+        if let Some(status) = &pools.status {
+            if !POOL_STATUSES.contains(&status.as_str()) {
+                problems.push(format!(
+                    "ingestion.pools.status {:?} must be one of {}",
+                    status,
+                    POOL_STATUSES.join(", ")
+                ));
+            }
+        }
+        for pool in &pools.allowlist {
+            if pools.denylist.contains(pool) {
+                problems.push(format!(
+                    "pool {} is on both ingestion.pools.allowlist and denylist",
+                    pool
+                ));
+            }
+        }
+
+        let backfill = &self.ingestion.backfill;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        if let Some(from) = backfill.from {
+            if from >= now {
+                problems.push("ingestion.backfill.from is in the future".to_string());
+            }
+            if backfill.to.is_some_and(|to| to <= from) {
+                problems.push("ingestion.backfill.to must be after backfill.from".to_string());
+            }
+        } else if backfill.to.is_some() {
+            problems.push("ingestion.backfill.to is set without backfill.from".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid() -> Config {
+        let mut config = Config::default();
+        config.database.url = Some("postgres://localhost/midgard".to_string());
+        config
+    }
+
+    #[test]
+    fn parses_a_full_file() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            bind = "0.0.0.0"
+            port = 9090
+
+            [database]
+            url = "postgres://localhost/midgard"
+            max_connections = 4
+
+            [midgard]
+            base_urls = ["https://midgard.ninerealms.com", "https://midgard.thorchain.info"]
+
+            [ingestion.depths]
+            repair_gaps = true
+
+            [ingestion.swaps]
+            enabled = false
+            repair_gaps = true
+            on_inconsistency = "reject"
+
+            [ingestion.pools]
+            status = "available"
+            denylist = ["ETH.USDT-0XDAC17F958D2EE523A2206206994597C13D831EC7"]
+
+            [ingestion.backfill]
+            from = "2024-05-01"
+            to = 1717200000
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.addr().to_string(), "0.0.0.0:9090");
+        assert_eq!(config.midgard.base_urls.len(), 2);
+        assert!(config.ingestion.depths.repair_gaps);
+        assert!(!config.ingestion.swaps.enabled);
+        assert!(config.ingestion.swaps.repair_gaps);
+        assert_eq!(
+            config.ingestion.swaps.on_inconsistency,
+            OnInconsistency::Reject
+        );
+        assert!(config.ingestion.runepool.enabled);
+        assert_eq!(config.ingestion.backfill.from, Some(1_714_521_600));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn example_file_is_valid() {
+        let mut config: Config = toml::from_str(include_str!("../config.example.toml")).unwrap();
+        config.database.url = Some("postgres://localhost/midgard".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let error = toml::from_str::<Config>("[server]\nprot = 1\n").unwrap_err();
+        assert!(error.to_string().contains("unknown field `prot`"));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let mut config = valid();
+        config.server.port = 0;
+        config.midgard.base_urls = vec!["midgard.local".to_string()];
+        config.ingestion.pools.allowlist = vec!["BTC.BTC".to_string()];
+        config.ingestion.pools.denylist = vec!["BTC.BTC".to_string()];
+        config.ingestion.backfill.from = Some(1_717_200_000);
+        config.ingestion.backfill.to = Some(1_714_521_600);
+
+        let ConfigError::Invalid(problems) = config.validate().unwrap_err() else {
+            panic!("expected validation problems");
+        };
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+    }
+
+    #[test]
+    fn pool_selection_denylist_wins() {
+        let selection = PoolSelection {
+            status: None,
+            allowlist: vec!["BTC.BTC".to_string(), "ETH.ETH".to_string()],
+            denylist: vec!["ETH.ETH".to_string()],
+        };
+        assert!(selection.admits("BTC.BTC"));
+        assert!(!selection.admits("ETH.ETH"));
+        assert!(!selection.admits("AVAX.AVAX"));
+        assert!(PoolSelection::default().admits("AVAX.AVAX"));
+    }
+
+    #[test]
+    fn parses_dates_as_utc_midnight() {
+        assert_eq!(parse_unix_or_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_unix_or_date("2024-02-29"), Ok(1_709_164_800));
+        assert_eq!(parse_unix_or_date("1714521600"), Ok(1_714_521_600));
+        assert!(parse_unix_or_date("2024-13-01").is_err());
+        assert!(parse_unix_or_date("2024-02-31").is_err());
+        assert!(parse_unix_or_date("2023-02-29").is_err());
+        assert!(parse_unix_or_date("yesterday").is_err());
+    }
+}
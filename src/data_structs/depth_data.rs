@@ -1,3 +1,4 @@
+use bigdecimal::BigDecimal;
 use serde::{Deserialize, Deserializer};
 use std::str::FromStr;
 
@@ -10,12 +11,19 @@ where
     u128::from_str(s).map_err(serde::de::Error::custom)
 }
 
-fn string_to_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
+// Prices, luvi and the meta ratios keep every digit Midgard sends; Midgard's "NaN" (e.g. a
+// pool without depth yet) reads as None and is stored as NULL
+fn string_to_decimal<'de, D>(deserializer: D) -> Result<Option<BigDecimal>, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: &str = Deserialize::deserialize(deserializer)?;
-    f64::from_str(s).map_err(serde::de::Error::custom)
+    if s == "NaN" {
+        return Ok(None);
+    }
+    BigDecimal::from_str(s)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
 }
 
 // Meta struct
@@ -25,10 +33,10 @@ pub struct RunePoolMeta {
     pub startTime: u128,
     #[serde(deserialize_with = "string_to_u128")]
     pub endTime: u128,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub priceShiftLoss: f64,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub luviIncrease: f64,
+    #[serde(deserialize_with = "string_to_decimal")]
+    pub priceShiftLoss: Option<BigDecimal>,
+    #[serde(deserialize_with = "string_to_decimal")]
+    pub luviIncrease: Option<BigDecimal>,
     #[serde(deserialize_with = "string_to_u128")]
     pub startAssetDepth: u128,
     #[serde(deserialize_with = "string_to_u128")]
@@ -62,10 +70,10 @@ pub struct RunePoolInterval {
     pub assetDepth: u128,
     #[serde(deserialize_with = "string_to_u128")]
     pub runeDepth: u128,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub assetPrice: f64,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub assetPriceUSD: f64,
+    #[serde(deserialize_with = "string_to_decimal")]
+    pub assetPrice: Option<BigDecimal>,
+    #[serde(deserialize_with = "string_to_decimal")]
+    pub assetPriceUSD: Option<BigDecimal>,
     #[serde(deserialize_with = "string_to_u128")]
     pub liquidityUnits: u128,
     #[serde(deserialize_with = "string_to_u128")]
@@ -76,8 +84,8 @@ pub struct RunePoolInterval {
     pub synthSupply: u128,
     #[serde(deserialize_with = "string_to_u128")]
     pub units: u128,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub luvi: f64,
+    #[serde(deserialize_with = "string_to_decimal")]
+    pub luvi: Option<BigDecimal>,
 }
 
 // Top-level struct
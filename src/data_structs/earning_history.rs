@@ -10,6 +10,14 @@ where
     u128::from_str(s).map_err(serde::de::Error::custom)
 }
 
+fn string_to_i128<'de, D>(deserializer: D) -> Result<i128, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: &str = Deserialize::deserialize(deserializer)?;
+    i128::from_str(s).map_err(serde::de::Error::custom)
+}
+
 fn string_to_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
 where
     D: Deserializer<'de>,
@@ -18,22 +26,22 @@ where
     f64::from_str(s).map_err(serde::de::Error::custom)
 }
 
-// Struct for Pool data
+// Struct for Pool data, rewards can be negative so amounts are signed
 #[derive(Deserialize, Debug)]
 pub struct PoolData {
     pub pool: String,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub assetLiquidityFees: f64,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub runeLiquidityFees: f64,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub totalLiquidityFeesRune: f64,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub saverEarning: f64,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub rewards: f64,
-    #[serde(deserialize_with = "string_to_f64")]
-    pub earnings: f64,
+    #[serde(deserialize_with = "string_to_i128")]
+    pub assetLiquidityFees: i128,
+    #[serde(deserialize_with = "string_to_i128")]
+    pub runeLiquidityFees: i128,
+    #[serde(deserialize_with = "string_to_i128")]
+    pub totalLiquidityFeesRune: i128,
+    #[serde(deserialize_with = "string_to_i128")]
+    pub saverEarning: i128,
+    #[serde(deserialize_with = "string_to_i128")]
+    pub rewards: i128,
+    #[serde(deserialize_with = "string_to_i128")]
+    pub earnings: i128,
 }
 
 // Struct for Meta data
@@ -57,7 +65,6 @@ pub struct RunePoolMeta {
     pub avgNodeCount: f64,
     #[serde(deserialize_with = "string_to_f64")]
     pub runePriceUSD: f64,
-    pub pools: Vec<PoolData>,
 }
 
 // Struct for Interval data
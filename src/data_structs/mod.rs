@@ -1,4 +1,5 @@
 pub mod depth_data;
 pub mod earning_history;
+pub mod pools;
 pub mod rune_pool;
 pub mod swap_history;
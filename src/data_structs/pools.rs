@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+// Entry of Midgard's `/v2/pools` list, only the fields ingestion needs
+#[derive(Deserialize, Debug)]
+pub struct PoolDetail {
+    pub asset: String,
+}
@@ -139,11 +139,47 @@ pub struct RunePoolInterval {
     pub synthRedeemVolume: u128,
     #[serde(deserialize_with = "string_to_u128")]
     pub totalVolume: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub toAssetVolumeUSD: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub toRuneVolumeUSD: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub toTradeVolumeUSD: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub fromTradeVolumeUSD: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub synthMintVolumeUSD: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub synthRedeemVolumeUSD: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub totalVolumeUSD: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub toAssetFees: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub toRuneFees: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub toTradeFees: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub fromTradeFees: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub synthMintFees: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub synthRedeemFees: u128,
+    #[serde(deserialize_with = "string_to_u128")]
+    pub totalFees: u128,
     #[serde(deserialize_with = "string_to_f64")]
     pub toAssetAverageSlip: f64,
     #[serde(deserialize_with = "string_to_f64")]
     pub toRuneAverageSlip: f64,
     #[serde(deserialize_with = "string_to_f64")]
+    pub toTradeAverageSlip: f64,
+    #[serde(deserialize_with = "string_to_f64")]
+    pub fromTradeAverageSlip: f64,
+    #[serde(deserialize_with = "string_to_f64")]
+    pub synthMintAverageSlip: f64,
+    #[serde(deserialize_with = "string_to_f64")]
+    pub synthRedeemAverageSlip: f64,
+    #[serde(deserialize_with = "string_to_f64")]
     pub averageSlip: f64,
     #[serde(deserialize_with = "string_to_f64")]
     pub runePriceUSD: f64,
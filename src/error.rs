@@ -0,0 +1,172 @@
+use crate::{cli, request_id};
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+// Errors shared by the ingestion pipeline and the API handlers
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Midgard request failed: {0}")]
+    Fetch(reqwest::Error),
+    #[error("Midgard response could not be decoded: {0}")]
+    Decode(String),
+    #[error("Midgard response rejected: {0}")]
+    Inconsistent(String),
+    #[error("{field} {value} too large for i64")]
+    Overflow { field: &'static str, value: u128 },
+    #[error("{0}")]
+    Validation(String),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("database migration failed: {0}")]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0} not found")]
+    NotFound(String),
+}
+
+// Decode failures surface as reqwest errors too, but retrying or reporting
+// Midgard as down would be wrong for them
+impl From<reqwest::Error> for AppError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_decode() {
+            AppError::Decode(error.to_string())
+        } else {
+            AppError::Fetch(error)
+        }
+    }
+}
+
+// The body of every error response
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    code: &'static str,
+    message: String,
+    request_id: Option<String>,
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Overflow { .. } | AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) | AppError::Database(sqlx::Error::RowNotFound) => {
+                StatusCode::NOT_FOUND
+            }
+            AppError::Fetch(_)
+            | AppError::Database(
+                sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_),
+            ) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Decode(_)
+            | AppError::Inconsistent(_)
+            | AppError::Database(_)
+            | AppError::Migrate(_)
+            | AppError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // process exit code when a CLI command stops on this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AppError::Validation(_) => cli::EXIT_USAGE,
+            _ if self.status() == StatusCode::SERVICE_UNAVAILABLE => cli::EXIT_UNAVAILABLE,
+            _ => cli::EXIT_FAILURE,
+        }
+    }
+
+    // Stable, machine readable counterpart of the variant
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Fetch(_) => "upstream_unavailable",
+            AppError::Decode(_) => "upstream_decode_error",
+            AppError::Inconsistent(_) => "upstream_inconsistent",
+            AppError::Overflow { .. } => "numeric_overflow",
+            AppError::Validation(_) => "invalid_request",
+            AppError::Database(sqlx::Error::RowNotFound) | AppError::NotFound(_) => "not_found",
+            AppError::Database(_) => "database_error",
+            AppError::Migrate(_) => "migration_error",
+            AppError::Io(_) => "io_error",
+        }
+    }
+
+    // What API clients get to see; server-side details only go to the log
+    fn public_message(&self) -> String {
+        match self {
+            AppError::Overflow { .. } | AppError::Validation(_) | AppError::NotFound(_) => {
+                self.to_string()
+            }
+            AppError::Database(sqlx::Error::RowNotFound) => "record not found".to_string(),
+            AppError::Fetch(_) => "Midgard is unavailable, try again later".to_string(),
+            AppError::Decode(_) | AppError::Inconsistent(_) => {
+                "Midgard returned an unexpected response".to_string()
+            }
+            AppError::Database(_) | AppError::Migrate(_) => "internal database error".to_string(),
+            AppError::Io(_) => "internal server error".to_string(),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let request_id = request_id::current();
+        if status.is_server_error() {
+            eprintln!(
+                "Request {} failed: {:?}",
+                request_id.as_deref().unwrap_or("-"),
+                self
+            );
+        }
+        let body = ErrorBody {
+            code: self.code(),
+            message: self.public_message(),
+            request_id,
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+// Fallback for paths no route matches, so they get the JSON error body too
+pub async fn route_not_found(uri: Uri) -> AppError {
+    AppError::NotFound(uri.path().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_of(error: AppError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn maps_variants_to_status_and_code() {
+        let (status, body) = body_of(AppError::Validation("count must be positive".into())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+        assert_eq!(body["message"], "count must be positive");
+
+        let (status, body) = body_of(AppError::Database(sqlx::Error::RowNotFound)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+
+        let (status, _) = body_of(AppError::Database(sqlx::Error::PoolTimedOut)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn hides_database_details() {
+        let error = AppError::Database(sqlx::Error::Protocol("relation \"secret\"".into()));
+        let (status, body) = body_of(error).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "database_error");
+        assert_eq!(body["message"], "internal database error");
+        assert!(body["request_id"].is_null());
+    }
+}
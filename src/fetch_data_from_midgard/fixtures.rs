@@ -0,0 +1,134 @@
+use crate::error::AppError;
+use serde::Deserialize;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// How the client gets Midgard responses: from the network, from a directory
+// of recorded responses, or from the network while saving each one there
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureMode {
+    #[default]
+    Live,
+    Replay,
+    Record,
+}
+
+impl FromStr for FixtureMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "live" => Ok(FixtureMode::Live),
+            "replay" => Ok(FixtureMode::Replay),
+            "record" => Ok(FixtureMode::Record),
+            _ => Err(format!(
+                "unknown fixture mode {:?}, expected live, replay or record",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub enum ResponseSource {
+    #[default]
+    Live,
+    Replay(PathBuf),
+    Record(PathBuf),
+}
+
+impl ResponseSource {
+    pub fn new(mode: FixtureMode, dir: &Path) -> Self {
+        match mode {
+            FixtureMode::Live => ResponseSource::Live,
+            FixtureMode::Replay => ResponseSource::Replay(dir.to_path_buf()),
+            FixtureMode::Record => ResponseSource::Record(dir.to_path_buf()),
+        }
+    }
+}
+
+// Query parameters that make up a fixture's name, in this order
+const KEY_PARAMS: [&str; 6] = ["pool", "status", "interval", "from", "to", "count"];
+
+// File name a request is recorded under: the endpoint, then the pool,
+// interval and time window, e.g. `/v2/history/swaps?interval=hour&count=3&pool=BTC.BTC`
+// is `swaps_BTC.BTC_hour_count3.json`. Replay only finds exact matches.
+pub fn fixture_name(path: &str, query: &[(&'static str, String)]) -> String {
+    let endpoint = path
+        .strip_prefix("/v2/history/")
+        .or_else(|| path.strip_prefix("/v2/"))
+        .unwrap_or(path);
+    let mut name = endpoint.trim_matches('/').replace('/', "_");
+    for key in KEY_PARAMS {
+        for (_, value) in query.iter().filter(|(k, _)| *k == key) {
+            name.push('_');
+            // numeric parameters keep their key so `from` and `to` stay apart
+            if matches!(key, "from" | "to" | "count") {
+                name.push_str(key);
+            }
+            name.push_str(&value.replace('/', "_"));
+        }
+    }
+    name + ".json"
+}
+
+pub fn fixture_path(dir: &Path, path: &str, query: &[(&'static str, String)]) -> PathBuf {
+    dir.join(fixture_name(path, query))
+}
+
+pub async fn load(
+    dir: &Path,
+    path: &str,
+    query: &[(&'static str, String)],
+) -> Result<String, AppError> {
+    let file = fixture_path(dir, path, query);
+    match tokio::fs::read_to_string(&file).await {
+        Ok(body) => Ok(body),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            Err(AppError::NotFound(format!("fixture {}", file.display())))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn save(
+    dir: &Path,
+    path: &str,
+    query: &[(&'static str, String)],
+    body: &str,
+) -> Result<(), AppError> {
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::write(fixture_path(dir, path, query), body).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_follow_endpoint_pool_interval_and_window() {
+        let query = [
+            ("interval", "hour".to_string()),
+            ("from", "1714521600".to_string()),
+            ("to", "1714532400".to_string()),
+        ];
+        assert_eq!(
+            fixture_name("/v2/history/depths/BTC.BTC", &query),
+            "depths_BTC.BTC_hour_from1714521600_to1714532400.json"
+        );
+
+        let query = [
+            ("interval", "hour".to_string()),
+            ("count", "3".to_string()),
+            ("pool", "BTC.BTC".to_string()),
+        ];
+        assert_eq!(
+            fixture_name("/v2/history/swaps", &query),
+            "swaps_BTC.BTC_hour_count3.json"
+        );
+        assert_eq!(fixture_name("/v2/pools", &[]), "pools.json");
+    }
+}
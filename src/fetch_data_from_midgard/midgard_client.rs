@@ -0,0 +1,496 @@
+use crate::config::MidgardConfig;
+use crate::data_structs::depth_data::RootDepthDetails;
+use crate::data_structs::earning_history::RootEarnDetails;
+use crate::data_structs::pools::PoolDetail;
+use crate::data_structs::rune_pool::RunePoolIntervalsInt;
+use crate::data_structs::swap_history::RootSwapDetails;
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::fixtures::{self, ResponseSource};
+use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+use crate::fetch_data_from_midgard::retry::{
+    is_retryable_error, is_retryable_status, retry_after, RetryPolicy,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_MIDGARD_BASE_URL: &str = "https://midgard.ninerealms.com";
+
+// Bucket sizes accepted by Midgard's `interval` query parameter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interval {
+    FiveMin,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Interval {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::FiveMin => "5min",
+            Interval::Hour => "hour",
+            Interval::Day => "day",
+            Interval::Week => "week",
+            Interval::Month => "month",
+            Interval::Quarter => "quarter",
+            Interval::Year => "year",
+        }
+    }
+
+    // Width in seconds for the fixed-size buckets; calendar buckets return None
+    pub fn fixed_seconds(&self) -> Option<i64> {
+        match self {
+            Interval::FiveMin => Some(300),
+            Interval::Hour => Some(3_600),
+            Interval::Day => Some(86_400),
+            Interval::Week | Interval::Month | Interval::Quarter | Interval::Year => None,
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "5min" => Ok(Interval::FiveMin),
+            "hour" => Ok(Interval::Hour),
+            "day" => Ok(Interval::Day),
+            "week" => Ok(Interval::Week),
+            "month" => Ok(Interval::Month),
+            "quarter" => Ok(Interval::Quarter),
+            "year" => Ok(Interval::Year),
+            _ => Err(format!(
+                "unknown interval {:?}, expected 5min, hour, day, week, month, quarter or year",
+                value
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+// Query parameters shared by every `/v2/history/*` endpoint
+#[derive(Clone, Debug, Default)]
+pub struct HistoryParams {
+    pub interval: Option<Interval>,
+    pub count: Option<u32>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl HistoryParams {
+    pub fn new(interval: Interval) -> Self {
+        HistoryParams {
+            interval: Some(interval),
+            ..Default::default()
+        }
+    }
+
+    fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        if let Some(interval) = self.interval {
+            pairs.push(("interval", interval.as_str().to_string()));
+        }
+        if let Some(count) = self.count {
+            pairs.push(("count", count.to_string()));
+        }
+        if let Some(from) = self.from {
+            pairs.push(("from", from.to_string()));
+        }
+        if let Some(to) = self.to {
+            pairs.push(("to", to.to_string()));
+        }
+        pairs
+    }
+}
+
+// A response body exactly as Midgard sent it, kept for archival
+#[derive(Clone, Debug)]
+pub struct RawResponse {
+    pub url: String,
+    pub status: u16,
+    pub fetched_at: SystemTime,
+    pub body: String,
+}
+
+// A decoded history payload together with the response it came from
+#[derive(Debug)]
+pub struct Fetched<T> {
+    pub data: T,
+    pub raw: RawResponse,
+}
+
+// Clones share the HTTP connection pool and the rate limiter
+#[derive(Clone, Debug)]
+pub struct MidgardClient {
+    base_urls: Vec<String>,
+    http: reqwest::Client,
+    policy: RetryPolicy,
+    limiter: Arc<RateLimiter>,
+    source: ResponseSource,
+}
+
+impl MidgardClient {
+    // Retries rotate through `base_urls`, so extra mirrors act as failover
+    pub fn with_policy(base_urls: Vec<String>, policy: RetryPolicy, limiter: RateLimiter) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(policy.timeout)
+            .build()
+            .expect("failed to build the Midgard HTTP client");
+        MidgardClient {
+            base_urls: base_urls
+                .into_iter()
+                .map(|url| url.trim_end_matches('/').to_string())
+                .collect(),
+            http,
+            policy,
+            limiter: Arc::new(limiter),
+            source: ResponseSource::Live,
+        }
+    }
+
+    pub fn with_source(self, source: ResponseSource) -> Self {
+        MidgardClient { source, ..self }
+    }
+
+    pub fn from_config(config: &MidgardConfig) -> Self {
+        let policy = RetryPolicy {
+            max_retries: config.max_retries,
+            timeout: Duration::from_secs(config.timeout_secs),
+            ..RetryPolicy::default()
+        };
+        MidgardClient::with_policy(
+            config.base_urls.clone(),
+            policy,
+            RateLimiter::new(config.requests_per_second, 1),
+        )
+        .with_source(ResponseSource::new(
+            config.fixture_mode,
+            &config.fixture_dir,
+        ))
+    }
+
+    // Rate limited GET that retries throttling, 5xx responses and transport
+    // failures, returning the last error once `max_retries` is used up
+    async fn get_raw(
+        &self,
+        path: &str,
+        query: &[(&'static str, String)],
+    ) -> Result<RawResponse, reqwest::Error> {
+        let mut attempt = 0;
+        loop {
+            let base_url = &self.base_urls[attempt as usize % self.base_urls.len()];
+            let url = format!("{}{}", base_url, path);
+            self.limiter.acquire().await;
+            let can_retry = attempt < self.policy.max_retries;
+            let (delay, reason) = match self.http.get(&url).query(query).send().await {
+                Ok(response) if can_retry && is_retryable_status(response.status()) => (
+                    retry_after(response.headers(), self.policy.max_delay)
+                        .unwrap_or_else(|| self.policy.backoff(attempt)),
+                    response.status().to_string(),
+                ),
+                Ok(response) => {
+                    let response = response.error_for_status()?;
+                    let url = response.url().to_string();
+                    let status = response.status().as_u16();
+                    match response.text().await {
+                        Ok(body) => {
+                            return Ok(RawResponse {
+                                url,
+                                status,
+                                fetched_at: SystemTime::now(),
+                                body,
+                            })
+                        }
+                        Err(e) if can_retry && is_retryable_error(&e) => {
+                            (self.policy.backoff(attempt), e.to_string())
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                Err(e) if can_retry && is_retryable_error(&e) => {
+                    (self.policy.backoff(attempt), e.to_string())
+                }
+                Err(e) => return Err(e),
+            };
+            attempt += 1;
+            eprintln!(
+                "Midgard request {}{} failed ({}), retry {} of {} in {:?}",
+                base_url, path, reason, attempt, self.policy.max_retries, delay
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    // Replay mode reads the recorded body instead of calling Midgard at all
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&'static str, String)],
+    ) -> Result<Fetched<T>, AppError> {
+        let raw = match &self.source {
+            ResponseSource::Live => self.get_raw(path, query).await?,
+            ResponseSource::Replay(dir) => RawResponse {
+                url: format!(
+                    "file://{}",
+                    fixtures::fixture_path(dir, path, query).display()
+                ),
+                status: 200,
+                fetched_at: SystemTime::now(),
+                body: fixtures::load(dir, path, query).await?,
+            },
+            ResponseSource::Record(dir) => {
+                let raw = self.get_raw(path, query).await?;
+                fixtures::save(dir, path, query, &raw.body).await?;
+                raw
+            }
+        };
+        let data = serde_json::from_str(&raw.body)
+            .map_err(|e| AppError::Decode(format!("{}: {}", path, e)))?;
+        Ok(Fetched { data, raw })
+    }
+
+    pub async fn runepool_history(
+        &self,
+        params: &HistoryParams,
+    ) -> Result<Fetched<RunePoolIntervalsInt>, AppError> {
+        self.get_json("/v2/history/runepool", &params.query_pairs())
+            .await
+    }
+
+    pub async fn depth_history(
+        &self,
+        pool: &str,
+        params: &HistoryParams,
+    ) -> Result<Fetched<RootDepthDetails>, AppError> {
+        self.get_json(
+            &format!("/v2/history/depths/{}", pool),
+            &params.query_pairs(),
+        )
+        .await
+    }
+
+    pub async fn earnings_history(
+        &self,
+        params: &HistoryParams,
+    ) -> Result<Fetched<RootEarnDetails>, AppError> {
+        self.get_json("/v2/history/earnings", &params.query_pairs())
+            .await
+    }
+
+    // `pool` narrows the series to one pool, None is the all-pools aggregate
+    pub async fn swap_history(
+        &self,
+        pool: Option<&str>,
+        params: &HistoryParams,
+    ) -> Result<Fetched<RootSwapDetails>, AppError> {
+        let mut query = params.query_pairs();
+        if let Some(pool) = pool {
+            query.push(("pool", pool.to_string()));
+        }
+        self.get_json("/v2/history/swaps", &query).await
+    }
+
+    // Lists Midgard's pools, optionally only those with the given status
+    // (e.g. "available" or "staged")
+    pub async fn pools(&self, status: Option<&str>) -> Result<Vec<PoolDetail>, AppError> {
+        let query: Vec<(&'static str, String)> = status
+            .map(|status| vec![("status", status.to_string())])
+            .unwrap_or_default();
+        Ok(self.get_json("/v2/pools", &query).await?.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{header::RETRY_AFTER, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // What the mock does for one request before it starts answering normally
+    #[derive(Clone)]
+    enum Failure {
+        Status(StatusCode, Option<&'static str>),
+        Stall(Duration),
+    }
+
+    #[derive(Clone)]
+    struct Mock {
+        failures: Arc<Vec<Failure>>,
+        hits: Arc<AtomicUsize>,
+    }
+
+    async fn pools_handler(State(mock): State<Mock>) -> Response {
+        let hit = mock.hits.fetch_add(1, Ordering::SeqCst);
+        match mock.failures.get(hit) {
+            Some(Failure::Status(status, retry_after)) => match retry_after {
+                Some(seconds) => (*status, [(RETRY_AFTER, *seconds)]).into_response(),
+                None => status.into_response(),
+            },
+            Some(Failure::Stall(duration)) => {
+                tokio::time::sleep(*duration).await;
+                Json(serde_json::json!([{ "asset": "BTC.BTC" }])).into_response()
+            }
+            None => Json(serde_json::json!([{ "asset": "BTC.BTC" }])).into_response(),
+        }
+    }
+
+    // Serves `/v2/pools` on a free local port, failing as scripted first
+    async fn serve_mock(failures: Vec<Failure>) -> (String, Arc<AtomicUsize>) {
+        let mock = Mock {
+            failures: Arc::new(failures),
+            hits: Arc::new(AtomicUsize::new(0)),
+        };
+        let hits = mock.hits.clone();
+        let app = Router::new()
+            .route("/v2/pools", get(pools_handler))
+            .with_state(mock);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), hits)
+    }
+
+    fn fast_client(base_urls: Vec<String>) -> MidgardClient {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(1_500),
+            timeout: Duration::from_millis(200),
+        };
+        MidgardClient::with_policy(base_urls, policy, RateLimiter::new(1_000.0, 10))
+    }
+
+    async fn mock_midgard(failures: Vec<Failure>) -> (MidgardClient, Arc<AtomicUsize>) {
+        let (base_url, hits) = serve_mock(failures).await;
+        (fast_client(vec![base_url]), hits)
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_success() {
+        let (client, hits) = mock_midgard(vec![
+            Failure::Status(StatusCode::SERVICE_UNAVAILABLE, None),
+            Failure::Status(StatusCode::BAD_GATEWAY, None),
+        ])
+        .await;
+
+        let pools = client.pools(None).await.unwrap();
+        assert_eq!(pools[0].asset, "BTC.BTC");
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn waits_for_retry_after_on_429() {
+        let (client, hits) = mock_midgard(vec![Failure::Status(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some("1"),
+        )])
+        .await;
+
+        let started = Instant::now();
+        client.pools(None).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn caps_retry_after_at_max_delay() {
+        let (client, hits) = mock_midgard(vec![Failure::Status(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some("3600"),
+        )])
+        .await;
+
+        let started = Instant::now();
+        client.pools(None).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_bodies_cut_short() {
+        // the first response promises more body than it sends
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let body = r#"[{"asset":"BTC.BTC"}]"#;
+            for length in [body.len() + 10, body.len()] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await.unwrap();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    length, body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let pools = fast_client(vec![base_url]).pools(None).await.unwrap();
+        assert_eq!(pools[0].asset, "BTC.BTC");
+    }
+
+    #[tokio::test]
+    async fn retries_timeouts() {
+        let (client, hits) = mock_midgard(vec![Failure::Stall(Duration::from_secs(1))]).await;
+
+        client.pools(None).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let (client, hits) =
+            mock_midgard(vec![
+                Failure::Status(StatusCode::INTERNAL_SERVER_ERROR, None);
+                10
+            ])
+            .await;
+
+        let Err(AppError::Fetch(error)) = client.pools(None).await else {
+            panic!("expected a fetch error");
+        };
+        assert_eq!(error.status(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let (client, hits) = mock_midgard(vec![Failure::Status(StatusCode::NOT_FOUND, None)]).await;
+
+        let Err(AppError::Fetch(error)) = client.pools(None).await else {
+            panic!("expected a fetch error");
+        };
+        assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_the_next_base_url() {
+        // nothing listens on port 1, so the first attempt cannot connect
+        let (base_url, hits) = serve_mock(vec![]).await;
+        let client = fast_client(vec!["http://127.0.0.1:1".to_string(), base_url]);
+
+        client.pools(None).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+}
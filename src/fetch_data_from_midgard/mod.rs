@@ -0,0 +1,4 @@
+pub mod fixtures;
+pub mod midgard_client;
+pub mod rate_limiter;
+pub mod retry;
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+// Token bucket shared by every clone of a `MidgardClient`, so all ingestion
+// tasks together stay under one request rate
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        RateLimiter {
+            requests_per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    // Waits until a token is available and takes it. The lock is held while
+    // waiting, which queues callers in arrival order.
+    pub async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+        loop {
+            let now = Instant::now();
+            let refill =
+                now.duration_since(bucket.refilled_at).as_secs_f64() * self.requests_per_second;
+            bucket.tokens = (bucket.tokens + refill).min(self.burst);
+            bucket.refilled_at = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return;
+            }
+            let wait = (1.0 - bucket.tokens) / self.requests_per_second;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spaces_requests_once_the_burst_is_spent() {
+        let limiter = RateLimiter::new(20.0, 2);
+        let started = Instant::now();
+        for _ in 0..6 {
+            limiter.acquire().await;
+        }
+        // 2 free from the burst, then 4 more at 50ms each
+        assert!(started.elapsed() >= Duration::from_millis(190));
+    }
+}
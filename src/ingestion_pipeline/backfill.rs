@@ -0,0 +1,148 @@
+use crate::config::Schedule;
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::midgard_client::{HistoryParams, MidgardClient};
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::ingest::{ingest_page, IntervalFilter, INGEST_INTERVAL};
+use crate::ingestion_pipeline::runs::{record_run, RowCounts, RunKind, RunRequest};
+use sqlx::PgPool;
+
+// Largest `count` Midgard accepts on the history endpoints
+pub const MIDGARD_MAX_PAGE_SIZE: u32 = 400;
+
+// Walks `dataset` backwards from `to` (or now) one full page at a time,
+// using each page's `meta.startTime` as the next `to`, until the page
+// reaches `from`. Only buckets from `from` on are stored, and those still
+// open at `now` are left for the poller. The whole walk is one run in the
+// ingestion ledger.
+#[allow(clippy::too_many_arguments)]
+pub async fn backfill(
+    midgard: &MidgardClient,
+    db: &PgPool,
+    dataset: Dataset,
+    pool: Option<&str>,
+    schedule: &Schedule,
+    from: i64,
+    to: Option<i64>,
+    now: i64,
+) -> Result<RowCounts, AppError> {
+    let request = RunRequest {
+        dataset,
+        pool,
+        kind: RunKind::Backfill,
+        from: Some(from),
+        to,
+    };
+    record_run(db, request, async {
+        let mut cursor = to;
+        let mut total = RowCounts::default();
+
+        loop {
+            let params = HistoryParams {
+                count: Some(MIDGARD_MAX_PAGE_SIZE),
+                to: cursor,
+                ..HistoryParams::new(INGEST_INTERVAL)
+            };
+            let page = ingest_page(
+                midgard,
+                db,
+                dataset,
+                pool,
+                &params,
+                IntervalFilter {
+                    after_start_time: Some(from - 1),
+                    ending_by: Some(now),
+                },
+                schedule.on_inconsistency,
+            )
+            .await?;
+            total += page.rows;
+
+            std::println!(
+                "Backfilled {} {} intervals from {} to {}",
+                page.interval_count,
+                dataset.as_str(),
+                page.meta_start_time,
+                page.meta_end_time
+            );
+
+            // Stop once we've covered `from`, Midgard has nothing older, or the cursor stalls
+            if page.interval_count == 0
+                || page.meta_start_time <= from
+                || cursor == Some(page.meta_start_time)
+            {
+                break;
+            }
+            cursor = Some(page.meta_start_time);
+        }
+
+        Ok(total)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+    use crate::ingestion_pipeline::scheduler::poll_once;
+    use crate::mock_midgard::server::{spawn, MockOptions};
+
+    // 2024-05-01 00:00 UTC
+    const HOUR: i64 = 1_714_521_600;
+
+    async fn midgard_at(now: i64) -> MidgardClient {
+        let base_url = spawn(MockOptions {
+            now: Some(now),
+            ..MockOptions::default()
+        })
+        .await;
+        MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        )
+    }
+
+    async fn stored_starts(db: &PgPool) -> Vec<i64> {
+        sqlx::query_scalar("SELECT startTime FROM Rune_Pool_Data_Intervals ORDER BY startTime")
+            .fetch_all(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn leaves_the_open_bucket_to_the_poller(db: PgPool) {
+        let schedule = Schedule::default();
+
+        // half way through the hour starting at HOUR
+        let now = HOUR + 1_800;
+        let midgard = midgard_at(now).await;
+        backfill(
+            &midgard,
+            &db,
+            Dataset::RunePool,
+            None,
+            &schedule,
+            HOUR - 3 * 3_600,
+            None,
+            now,
+        )
+        .await
+        .unwrap();
+        // the page reaches back past `from`, but only the three hours from
+        // it are kept
+        let stored = stored_starts(&db).await;
+        assert_eq!(stored, [HOUR - 3 * 3_600, HOUR - 2 * 3_600, HOUR - 3_600]);
+
+        // once it has closed the poller stores it, and rereads the latest
+        // stored hour in case that was stored while still open
+        let now = HOUR + 3_600 + 1_800;
+        let midgard = midgard_at(now).await;
+        let rows = poll_once(&midgard, &db, Dataset::RunePool, None, &schedule, now)
+            .await
+            .unwrap();
+        assert_eq!((rows.inserted, rows.updated), (1, 1));
+        assert_eq!(stored_starts(&db).await.last(), Some(&HOUR));
+    }
+}
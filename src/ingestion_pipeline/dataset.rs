@@ -0,0 +1,53 @@
+// The four Midgard history series this service mirrors
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Deserialize, utoipa::ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Dataset {
+    #[value(name = "runepool")]
+    RunePool,
+    Depths,
+    Earnings,
+    Swaps,
+}
+
+impl Dataset {
+    pub const ALL: [Dataset; 4] = [
+        Dataset::RunePool,
+        Dataset::Depths,
+        Dataset::Earnings,
+        Dataset::Swaps,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dataset::RunePool => "runepool",
+            Dataset::Depths => "depths",
+            Dataset::Earnings => "earnings",
+            Dataset::Swaps => "swaps",
+        }
+    }
+
+    // "depths BTC.BTC", or just the dataset for a pool-less series, for logs
+    pub fn series_label(&self, pool: Option<&str>) -> String {
+        match pool {
+            Some(pool) => format!("{} {}", self.as_str(), pool),
+            None => self.as_str().to_string(),
+        }
+    }
+
+    // Table holding the stored interval rows and its start/end time columns
+    pub fn intervals_table(&self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Dataset::RunePool => ("rune_pool_data_intervals", "startTime", "endTime"),
+            Dataset::Depths => ("rune_pool_depth_intervals", "startTime", "endTime"),
+            Dataset::Earnings => ("earning_data_rune_pool_interval", "start_time", "end_time"),
+            Dataset::Swaps => ("swap_data_rune_pool_interval", "start_time", "end_time"),
+        }
+    }
+
+    // Depths and swaps are stored per pool, keyed by a `pool` column
+    pub fn is_per_pool(&self) -> bool {
+        matches!(self, Dataset::Depths | Dataset::Swaps)
+    }
+}
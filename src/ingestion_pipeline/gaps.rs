@@ -0,0 +1,249 @@
+use crate::config::{PoolSelection, Schedule};
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::midgard_client::{HistoryParams, MidgardClient};
+use crate::ingestion_pipeline::backfill::MIDGARD_MAX_PAGE_SIZE;
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::ingest::{ingest_page, IntervalFilter, INGEST_INTERVAL};
+use crate::ingestion_pipeline::runs::{record_run, RowCounts, RunKind, RunRequest};
+use crate::insert_data_post_migration::swap_data_insert_script::AGGREGATE_POOL;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+
+// A hole in a stored series: nothing starts in [from, to), although the
+// intervals either side of it are stored. `missing` is how many buckets of
+// the width of the interval before the hole fit in it, which is only
+// approximate for the calendar intervals.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, FromRow, ToSchema)]
+pub struct Gap {
+    // None for the pool-less series, including the all-pools swaps; the
+    // API lists gaps under their series, so it is not repeated there
+    #[serde(skip)]
+    pub pool: Option<String>,
+    pub from: i64,
+    pub to: i64,
+    pub missing: i64,
+}
+
+// Every gap in `dataset`'s stored intervals, per pool for depths and swaps,
+// oldest first. Missing history before the first stored interval is not a
+// gap, that is what backfill is for.
+pub async fn find_gaps(db: &PgPool, dataset: Dataset) -> Result<Vec<Gap>, AppError> {
+    let (table, start, end) = dataset.intervals_table();
+    let pool = if dataset.is_per_pool() {
+        format!("NULLIF(pool, '{}')", AGGREGATE_POOL)
+    } else {
+        "NULL::text".to_string()
+    };
+    let gaps = sqlx::query_as::<_, Gap>(&format!(
+        r#"SELECT
+            pool,
+            {end} AS "from",
+            next_start AS "to",
+            GREATEST((next_start - {end}) / NULLIF({end} - {start}, 0), 1) AS missing
+        FROM (
+            SELECT {pool} AS pool, {start}, {end},
+                LEAD({start}) OVER (PARTITION BY {pool} ORDER BY {start}) AS next_start
+            FROM {table}
+        ) w
+        WHERE next_start > {end}
+        ORDER BY pool NULLS FIRST, {end}"#,
+        table = table,
+        start = start,
+        end = end,
+        pool = pool
+    ))
+    .fetch_all(db)
+    .await?;
+    Ok(gaps)
+}
+
+// Refetches just the window of `gap`, a page at a time, as one run in the
+// ingestion ledger. A bucket Midgard itself has no data for stays a gap.
+pub async fn repair_gap(
+    midgard: &MidgardClient,
+    db: &PgPool,
+    dataset: Dataset,
+    gap: &Gap,
+    schedule: &Schedule,
+) -> Result<RowCounts, AppError> {
+    let request = RunRequest {
+        dataset,
+        pool: gap.pool.as_deref(),
+        kind: RunKind::Repair,
+        from: Some(gap.from),
+        to: Some(gap.to),
+    };
+    let page_width =
+        INGEST_INTERVAL.fixed_seconds().unwrap_or(86_400) * MIDGARD_MAX_PAGE_SIZE as i64;
+    record_run(db, request, async {
+        let mut cursor = gap.from;
+        let mut total = RowCounts::default();
+
+        while cursor < gap.to {
+            let params = HistoryParams {
+                from: Some(cursor),
+                to: Some(gap.to.min(cursor + page_width)),
+                ..HistoryParams::new(INGEST_INTERVAL)
+            };
+            // the stored neighbours are left alone
+            let filter = IntervalFilter {
+                after_start_time: Some(cursor - 1),
+                ending_by: Some(gap.to),
+            };
+            let page = ingest_page(
+                midgard,
+                db,
+                dataset,
+                gap.pool.as_deref(),
+                &params,
+                filter,
+                schedule.on_inconsistency,
+            )
+            .await?;
+            total += page.rows;
+
+            if page.interval_count == 0 || page.meta_end_time <= cursor {
+                break;
+            }
+            cursor = page.meta_end_time;
+        }
+
+        Ok(total)
+    })
+    .await
+}
+
+// Finds and repairs the gaps of every series of `dataset` that `pools`
+// still admits; a failed repair is logged and the rest carry on
+pub async fn repair_gaps(
+    midgard: &MidgardClient,
+    db: &PgPool,
+    dataset: Dataset,
+    schedule: &Schedule,
+    pools: &PoolSelection,
+) -> Result<RowCounts, AppError> {
+    let mut total = RowCounts::default();
+    for gap in find_gaps(db, dataset).await? {
+        if gap.pool.as_deref().is_some_and(|pool| !pools.admits(pool)) {
+            continue;
+        }
+        let series = dataset.series_label(gap.pool.as_deref());
+        match repair_gap(midgard, db, dataset, &gap, schedule).await {
+            Ok(rows) => {
+                std::println!(
+                    "Repaired {} of {} missing {} intervals between {} and {}",
+                    rows.inserted,
+                    gap.missing,
+                    series,
+                    gap.from,
+                    gap.to
+                );
+                total += rows;
+            }
+            Err(e) => eprintln!(
+                "Repairing {} between {} and {} failed: {}",
+                series, gap.from, gap.to, e
+            ),
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch_data_from_midgard::midgard_client::Interval;
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+    use crate::ingestion_pipeline::validation::OnInconsistency;
+    use crate::mock_midgard::server::{spawn, MockOptions};
+
+    // 2024-05-01 00:00 UTC
+    const NOW: i64 = 1_714_521_600;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn finds_and_refills_missing_hours(db: PgPool) {
+        let base_url = spawn(MockOptions {
+            now: Some(NOW),
+            ..MockOptions::default()
+        })
+        .await;
+        let midgard = MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        );
+        let params = HistoryParams {
+            count: Some(24),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        ingest_page(
+            &midgard,
+            &db,
+            Dataset::Depths,
+            Some("BTC.BTC"),
+            &params,
+            IntervalFilter::default(),
+            OnInconsistency::Record,
+        )
+        .await
+        .unwrap();
+        ingest_page(
+            &midgard,
+            &db,
+            Dataset::Swaps,
+            None,
+            &params,
+            IntervalFilter::default(),
+            OnInconsistency::Record,
+        )
+        .await
+        .unwrap();
+        assert!(find_gaps(&db, Dataset::Depths).await.unwrap().is_empty());
+
+        sqlx::query("DELETE FROM rune_pool_depth_intervals WHERE startTime BETWEEN $1 AND $2")
+            .bind(NOW - 10 * 3_600)
+            .bind(NOW - 8 * 3_600)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM swap_data_rune_pool_interval WHERE start_time = $1")
+            .bind(NOW - 5 * 3_600)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let gaps = find_gaps(&db, Dataset::Depths).await.unwrap();
+        assert_eq!(
+            gaps,
+            vec![Gap {
+                pool: Some("BTC.BTC".to_string()),
+                from: NOW - 10 * 3_600,
+                to: NOW - 7 * 3_600,
+                missing: 3,
+            }]
+        );
+        assert_eq!(
+            find_gaps(&db, Dataset::Swaps).await.unwrap(),
+            vec![Gap {
+                pool: None,
+                from: NOW - 5 * 3_600,
+                to: NOW - 4 * 3_600,
+                missing: 1,
+            }]
+        );
+
+        let rows = repair_gaps(
+            &midgard,
+            &db,
+            Dataset::Depths,
+            &Schedule::default(),
+            &PoolSelection::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((rows.inserted, rows.updated), (3, 0));
+        assert!(find_gaps(&db, Dataset::Depths).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::config::PoolSelection;
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::midgard_client::{
+    Fetched, HistoryParams, Interval, MidgardClient,
+};
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::runs::RowCounts;
+use crate::ingestion_pipeline::validation::{self, OnInconsistency};
+use crate::insert_data_post_migration::{
+    depth_data_insert_script, earning_data_insert_script, rune_pool_data_insert_script,
+    swap_data_insert_script,
+};
+use sqlx::PgPool;
+
+// The Midgard bucket size every series is ingested at. Stored intervals are
+// keyed on their start time alone, so there is only one; days and longer
+// are rolled up from the hours when queried.
+pub const INGEST_INTERVAL: Interval = Interval::Hour;
+
+// What a single fetched-and-stored Midgard response covered
+#[derive(Debug)]
+pub struct IngestedPage {
+    pub meta_start_time: i64,
+    pub meta_end_time: i64,
+    pub interval_count: usize,
+    pub rows: RowCounts,
+}
+
+// Which fetched intervals get stored; the default keeps everything
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IntervalFilter {
+    pub after_start_time: Option<i64>,
+    pub ending_by: Option<i64>,
+}
+
+impl IntervalFilter {
+    fn keeps(&self, start_time: u128, end_time: u128) -> bool {
+        // any start is after a negative time
+        self.after_start_time
+            .and_then(|after| u128::try_from(after).ok())
+            .is_none_or(|after| start_time > after)
+            && self.ending_by.is_none_or(|by| end_time <= by as u128)
+    }
+
+    // Drops the intervals outside the filter and returns how many that was
+    fn retain<T>(&self, intervals: &mut Vec<T>, times: impl Fn(&T) -> (u128, u128)) -> usize {
+        let fetched = intervals.len();
+        intervals.retain(|interval| {
+            let (start_time, end_time) = times(interval);
+            self.keeps(start_time, end_time)
+        });
+        fetched - intervals.len()
+    }
+}
+
+fn to_i64(value: u128, field: &'static str) -> Result<i64, AppError> {
+    value
+        .try_into()
+        .map_err(|_| AppError::Overflow { field, value })
+}
+
+// The pools a dataset is ingested for: the Midgard pools `selection` admits
+// for depths, those plus the all-pools series (None) for swaps, and just the
+// pool-less series for everything else
+pub async fn pools_to_ingest(
+    midgard: &MidgardClient,
+    dataset: Dataset,
+    selection: &PoolSelection,
+) -> Result<Vec<Option<String>>, AppError> {
+    if let Dataset::RunePool | Dataset::Earnings = dataset {
+        return Ok(vec![None]);
+    }
+
+    let mut pools: Vec<Option<String>> = midgard
+        .pools(selection.status.as_deref())
+        .await?
+        .into_iter()
+        .filter(|pool| selection.admits(&pool.asset))
+        .map(|pool| Some(pool.asset))
+        .collect();
+    if dataset == Dataset::Swaps {
+        pools.insert(0, None);
+    }
+    Ok(pools)
+}
+
+// Fetches one page of `dataset` and persists it through the insert scripts,
+// archiving the raw response in the same transaction as the rows decoded
+// from it. The whole payload is checked for meta/interval consistency before
+// `filter` applies. `pool` is required for depths, optional for swaps and
+// ignored otherwise.
+pub async fn ingest_page(
+    midgard: &MidgardClient,
+    db: &PgPool,
+    dataset: Dataset,
+    pool: Option<&str>,
+    params: &HistoryParams,
+    filter: IntervalFilter,
+    on_inconsistency: OnInconsistency,
+) -> Result<IngestedPage, AppError> {
+    let (meta_start_time, meta_end_time, interval_count, rows) = match dataset {
+        Dataset::RunePool => {
+            let Fetched { mut data, raw } = midgard.runepool_history(params).await?;
+            let issues = validation::check_runepool(&data);
+            let skipped = filter.retain(&mut data.intervals, |i| (i.startTime, i.endTime));
+            let (start, end, count) =
+                (data.meta.startTime, data.meta.endTime, data.intervals.len());
+            let (mut tx, raw_id) =
+                validation::archive(db, &raw, dataset, pool, &issues, on_inconsistency).await?;
+            let upserted =
+                rune_pool_data_insert_script::insert_data(&mut *tx, data, Some(raw_id)).await?;
+            tx.commit().await?;
+            (start, end, count, RowCounts::new(upserted, skipped))
+        }
+        Dataset::Depths => {
+            let pool =
+                pool.ok_or_else(|| AppError::Validation("depth history needs a pool".into()))?;
+            let Fetched { mut data, raw } = midgard.depth_history(pool, params).await?;
+            let issues = validation::check_depths(&data);
+            let skipped = filter.retain(&mut data.intervals, |i| (i.startTime, i.endTime));
+            let (start, end, count) =
+                (data.meta.startTime, data.meta.endTime, data.intervals.len());
+            let (mut tx, raw_id) =
+                validation::archive(db, &raw, dataset, Some(pool), &issues, on_inconsistency)
+                    .await?;
+            let upserted =
+                depth_data_insert_script::insert_data(&mut *tx, pool, data, Some(raw_id)).await?;
+            tx.commit().await?;
+            (start, end, count, RowCounts::new(upserted, skipped))
+        }
+        Dataset::Earnings => {
+            let Fetched { mut data, raw } = midgard.earnings_history(params).await?;
+            let issues = validation::check_earnings(&data);
+            let skipped = filter.retain(&mut data.intervals, |i| (i.startTime, i.endTime));
+            // meta and intervals come from the same payload, so they commit together
+            let (mut tx, raw_id) =
+                validation::archive(db, &raw, dataset, pool, &issues, on_inconsistency).await?;
+            earning_data_insert_script::insert_rune_pool_meta(&data.meta, &mut *tx).await?;
+            let upserted = earning_data_insert_script::insert_rune_pool_intervals(
+                &data.intervals,
+                Some(raw_id),
+                &mut *tx,
+            )
+            .await?;
+            tx.commit().await?;
+            (
+                data.meta.startTime,
+                data.meta.endTime,
+                data.intervals.len(),
+                RowCounts::new(upserted, skipped),
+            )
+        }
+        Dataset::Swaps => {
+            let Fetched { mut data, raw } = midgard.swap_history(pool, params).await?;
+            let issues = validation::check_swaps(&data);
+            let skipped = filter.retain(&mut data.intervals, |i| (i.startTime, i.endTime));
+            let (mut tx, raw_id) =
+                validation::archive(db, &raw, dataset, pool, &issues, on_inconsistency).await?;
+            let pool = pool.unwrap_or(swap_data_insert_script::AGGREGATE_POOL);
+            swap_data_insert_script::insert_rune_pool_meta(&data.meta, pool, &mut *tx).await?;
+            let upserted = swap_data_insert_script::insert_rune_pool_intervals(
+                &data.intervals,
+                pool,
+                Some(raw_id),
+                &mut *tx,
+            )
+            .await?;
+            tx.commit().await?;
+            (
+                data.meta.startTime,
+                data.meta.endTime,
+                data.intervals.len(),
+                RowCounts::new(upserted, skipped),
+            )
+        }
+    };
+
+    Ok(IngestedPage {
+        meta_start_time: to_i64(meta_start_time, "meta startTime")?,
+        meta_end_time: to_i64(meta_end_time, "meta endTime")?,
+        interval_count,
+        rows,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch_data_from_midgard::fixtures::ResponseSource;
+    use crate::fetch_data_from_midgard::midgard_client::Interval;
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+
+    // Answers from the recorded responses in `fixtures/`, never the network
+    fn replay_client() -> MidgardClient {
+        MidgardClient::with_policy(
+            vec!["http://127.0.0.1:1".to_string()],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        )
+        .with_source(ResponseSource::Replay(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures").into(),
+        ))
+    }
+
+    fn recorded_page() -> HistoryParams {
+        HistoryParams {
+            count: Some(3),
+            to: Some(1_714_532_400),
+            ..HistoryParams::new(Interval::Hour)
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn ingests_recorded_earnings_and_swaps(db: PgPool) {
+        let midgard = replay_client();
+        for (dataset, pool) in [(Dataset::Earnings, None), (Dataset::Swaps, Some("BTC.BTC"))] {
+            let page = ingest_page(
+                &midgard,
+                &db,
+                dataset,
+                pool,
+                &recorded_page(),
+                IntervalFilter::default(),
+                OnInconsistency::Record,
+            )
+            .await
+            .unwrap();
+            assert_eq!(page.interval_count, 3);
+            assert_eq!(page.meta_end_time, 1_714_532_400);
+        }
+
+        let earnings: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM earning_data_pool_data")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(earnings, 6);
+        let swaps: Vec<String> =
+            sqlx::query_scalar("SELECT pool FROM swap_data_rune_pool_interval")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+        assert_eq!(swaps, ["BTC.BTC"; 3]);
+        // recorded responses are Midgard's word for it: their meta adds up
+        let issues: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM data_quality_issues")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(issues, 0);
+
+        // every row points at the archived payload it was decoded from
+        let urls: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT r.url FROM swap_data_rune_pool_interval i \
+             JOIN ingest_raw_responses r ON r.id = i.raw_response_id",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(urls.len(), 1);
+        assert!(urls[0].ends_with("swaps_BTC.BTC_hour_to1714532400_count3.json"));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn replay_reports_missing_fixtures(db: PgPool) {
+        let params = HistoryParams::new(Interval::Day);
+        let error = ingest_page(
+            &replay_client(),
+            &db,
+            Dataset::RunePool,
+            None,
+            &params,
+            IntervalFilter::default(),
+            OnInconsistency::Record,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(error, AppError::NotFound(ref what) if what.ends_with("runepool_day.json"))
+        );
+    }
+}
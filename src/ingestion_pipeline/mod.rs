@@ -0,0 +1,7 @@
+pub mod backfill;
+pub mod dataset;
+pub mod gaps;
+pub mod ingest;
+pub mod runs;
+pub mod scheduler;
+pub mod validation;
@@ -0,0 +1,151 @@
+use crate::error::AppError;
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::insert_data_post_migration::Upserted;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::future::Future;
+use std::ops::AddAssign;
+use std::time::Instant;
+
+// What a run did with the intervals it fetched; skipped ones were outside
+// the requested window (e.g. the still-open current bucket)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RowCounts {
+    pub inserted: i64,
+    pub updated: i64,
+    pub skipped: i64,
+}
+
+impl RowCounts {
+    pub fn new(upserted: Upserted, skipped: usize) -> Self {
+        RowCounts {
+            inserted: upserted.inserted,
+            updated: upserted.updated,
+            skipped: skipped as i64,
+        }
+    }
+
+    // Intervals written, new or corrected
+    pub fn stored(&self) -> i64 {
+        self.inserted + self.updated
+    }
+}
+
+impl AddAssign for RowCounts {
+    fn add_assign(&mut self, other: RowCounts) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.skipped += other.skipped;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunKind {
+    Poll,
+    Backfill,
+    Repair,
+}
+
+impl RunKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RunKind::Poll => "poll",
+            RunKind::Backfill => "backfill",
+            RunKind::Repair => "repair",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl RunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Running => "running",
+            RunStatus::Succeeded => "succeeded",
+            RunStatus::Failed => "failed",
+        }
+    }
+}
+
+// One series and window an ingestion run was asked to cover
+#[derive(Clone, Copy, Debug)]
+pub struct RunRequest<'a> {
+    pub dataset: Dataset,
+    pub pool: Option<&'a str>,
+    pub kind: RunKind,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+// Records `run` in the `ingestion_runs` ledger: a 'running' row up front,
+// completed with its outcome once `run` finishes. A ledger write failing
+// is only logged, the run's own result is what gets returned.
+pub async fn record_run<F>(
+    db: &PgPool,
+    request: RunRequest<'_>,
+    run: F,
+) -> Result<RowCounts, AppError>
+where
+    F: Future<Output = Result<RowCounts, AppError>>,
+{
+    let started = Instant::now();
+    let id: Option<i64> = sqlx::query_scalar(
+        r#"
+        INSERT INTO ingestion_runs (dataset, pool, kind, requested_from, requested_to)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(request.dataset.as_str())
+    .bind(request.pool)
+    .bind(request.kind.as_str())
+    .bind(request.from)
+    .bind(request.to)
+    .fetch_one(db)
+    .await
+    .inspect_err(|e| eprintln!("Could not start an ingestion run record: {}", e))
+    .ok();
+
+    let result = run.await;
+
+    if let Some(id) = id {
+        let (status, counts, error) = match &result {
+            Ok(counts) => (RunStatus::Succeeded, Some(*counts), None),
+            Err(e) => (RunStatus::Failed, None, Some(e.to_string())),
+        };
+        let finished = sqlx::query(
+            r#"
+            UPDATE ingestion_runs SET
+                status = $2,
+                finished_at = now(),
+                duration_ms = $3,
+                rows_inserted = $4,
+                rows_updated = $5,
+                rows_skipped = $6,
+                error = $7
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status.as_str())
+        .bind(started.elapsed().as_millis() as i64)
+        .bind(counts.map(|c| c.inserted))
+        .bind(counts.map(|c| c.updated))
+        .bind(counts.map(|c| c.skipped))
+        .bind(error)
+        .execute(db)
+        .await;
+        if let Err(e) = finished {
+            eprintln!("Could not complete ingestion run {}: {}", id, e);
+        }
+    }
+
+    result
+}
@@ -0,0 +1,232 @@
+use crate::config::{IngestionConfig, PoolSelection, Schedule};
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::midgard_client::{HistoryParams, MidgardClient};
+use crate::ingestion_pipeline::backfill::MIDGARD_MAX_PAGE_SIZE;
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::gaps::repair_gaps;
+use crate::ingestion_pipeline::ingest::{
+    ingest_page, pools_to_ingest, IntervalFilter, INGEST_INTERVAL,
+};
+use crate::ingestion_pipeline::runs::{record_run, RowCounts, RunKind, RunRequest};
+use crate::insert_data_post_migration::swap_data_insert_script::AGGREGATE_POOL;
+use sqlx::PgPool;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Midgard needs a moment after a boundary before the closed bucket is final
+const POLL_GRACE_SECONDS: i64 = 30;
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Time left until the next ingest boundary plus the grace period
+fn until_next_boundary() -> Duration {
+    let width = INGEST_INTERVAL.fixed_seconds().unwrap_or(86_400);
+    let now = unix_now();
+    let next = (now / width + 1) * width + POLL_GRACE_SECONDS;
+    Duration::from_secs((next - now) as u64)
+}
+
+pub async fn latest_start_time(
+    db: &PgPool,
+    dataset: Dataset,
+    pool: Option<&str>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let (table, start_column, _) = dataset.intervals_table();
+    if dataset.is_per_pool() {
+        sqlx::query_scalar(&format!(
+            "SELECT MAX({}) FROM {} WHERE pool = $1",
+            start_column, table
+        ))
+        .bind(pool.unwrap_or(AGGREGATE_POOL))
+        .fetch_one(db)
+        .await
+    } else {
+        sqlx::query_scalar(&format!("SELECT MAX({}) FROM {}", start_column, table))
+            .fetch_one(db)
+            .await
+    }
+}
+
+// Fetches whatever intervals completed by `now` are newer than the latest
+// stored one, at most a page at a time, and the latest stored one again so
+// the upsert corrects it if it was stored before Midgard closed it. An
+// empty table starts from the most recent full page. The request does not
+// depend on `now`, so a recorded poll replays on any later tick.
+pub async fn poll_once(
+    midgard: &MidgardClient,
+    db: &PgPool,
+    dataset: Dataset,
+    pool: Option<&str>,
+    schedule: &Schedule,
+    now: i64,
+) -> Result<RowCounts, AppError> {
+    let latest = latest_start_time(db, dataset, pool).await?;
+    // Midgard stops a page at the current bucket
+    let params = HistoryParams {
+        from: latest,
+        count: Some(MIDGARD_MAX_PAGE_SIZE),
+        ..HistoryParams::new(INGEST_INTERVAL)
+    };
+    let filter = IntervalFilter {
+        after_start_time: latest.map(|latest| latest - 1),
+        ending_by: Some(now),
+    };
+
+    let request = RunRequest {
+        dataset,
+        pool,
+        kind: RunKind::Poll,
+        from: latest,
+        to: Some(now),
+    };
+    record_run(db, request, async {
+        let page = ingest_page(
+            midgard,
+            db,
+            dataset,
+            pool,
+            &params,
+            filter,
+            schedule.on_inconsistency,
+        )
+        .await?;
+        Ok(page.rows)
+    })
+    .await
+}
+
+async fn poll_dataset(
+    midgard: MidgardClient,
+    db: PgPool,
+    dataset: Dataset,
+    schedule: Schedule,
+    pools: PoolSelection,
+) {
+    loop {
+        // the pool list is re-read every tick so newly listed pools get picked up
+        match pools_to_ingest(&midgard, dataset, &pools).await {
+            Ok(pools) => {
+                for pool in pools {
+                    let series = dataset.series_label(pool.as_deref());
+                    match poll_once(
+                        &midgard,
+                        &db,
+                        dataset,
+                        pool.as_deref(),
+                        &schedule,
+                        unix_now(),
+                    )
+                    .await
+                    {
+                        Ok(rows) => std::println!(
+                            "Scheduled ingest stored {} new {} intervals",
+                            rows.inserted,
+                            series
+                        ),
+                        Err(e) => eprintln!("Scheduled ingest of {} failed: {:?}", series, e),
+                    }
+                }
+            }
+            Err(e) => eprintln!("Listing pools for {} failed: {:?}", dataset.as_str(), e),
+        }
+        if schedule.repair_gaps {
+            if let Err(e) = repair_gaps(&midgard, &db, dataset, &schedule, &pools).await {
+                eprintln!("Gap repair of {} failed: {:?}", dataset.as_str(), e);
+            }
+        }
+        tokio::time::sleep(until_next_boundary()).await;
+    }
+}
+
+// Spawns one hourly polling task per enabled dataset; they run for the life
+// of the process
+pub fn spawn_scheduler(midgard: MidgardClient, db: PgPool, config: &IngestionConfig) {
+    for dataset in Dataset::ALL {
+        let schedule = config.schedule(dataset);
+        if !schedule.enabled {
+            std::println!("Scheduled ingest of {} is disabled", dataset.as_str());
+            continue;
+        }
+        tokio::spawn(poll_dataset(
+            midgard.clone(),
+            db.clone(),
+            dataset,
+            schedule.clone(),
+            config.pools.clone(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch_data_from_midgard::fixtures::ResponseSource;
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+    use crate::mock_midgard::server::{spawn, MockOptions};
+
+    // 2024-05-01 00:00 UTC
+    const HOUR: i64 = 1_714_521_600;
+
+    fn client(base_url: String, source: ResponseSource) -> MidgardClient {
+        MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        )
+        .with_source(source)
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn recorded_polls_replay_on_later_ticks(db: PgPool) {
+        let dir = std::env::temp_dir().join(format!("poll_fixtures_{}", std::process::id()));
+        let schedule = Schedule::default();
+
+        let base_url = spawn(MockOptions {
+            now: Some(HOUR + 60),
+            ..MockOptions::default()
+        })
+        .await;
+        let recording = client(base_url, ResponseSource::Record(dir.clone()));
+        let mut recorded = Vec::new();
+        for _ in 0..2 {
+            let rows = poll_once(
+                &recording,
+                &db,
+                Dataset::RunePool,
+                None,
+                &schedule,
+                HOUR + 60,
+            )
+            .await
+            .unwrap();
+            recorded.push(rows.inserted);
+        }
+
+        // the same polls from an empty table, minutes later and with no
+        // Midgard to talk to
+        sqlx::query("DELETE FROM Rune_Pool_Data_Intervals")
+            .execute(&db)
+            .await
+            .unwrap();
+        let replaying = client(
+            "http://127.0.0.1:1".to_string(),
+            ResponseSource::Replay(dir.clone()),
+        );
+        let mut replayed = Vec::new();
+        for now in [HOUR + 600, HOUR + 1_200] {
+            let rows = poll_once(&replaying, &db, Dataset::RunePool, None, &schedule, now)
+                .await
+                .unwrap();
+            replayed.push(rows.inserted);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(recorded, [399, 0]);
+        assert_eq!(replayed, recorded);
+    }
+}
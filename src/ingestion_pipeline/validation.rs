@@ -0,0 +1,471 @@
+use crate::data_structs::{depth_data, earning_history, rune_pool, swap_history};
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::midgard_client::RawResponse;
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::insert_data_post_migration::raw_response_insert_script::insert_raw_response;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::str::FromStr;
+
+// What ingestion does with a payload whose meta and intervals disagree:
+// store it and record the issues, or record the issues and store nothing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnInconsistency {
+    #[default]
+    Record,
+    Reject,
+}
+
+impl FromStr for OnInconsistency {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "record" => Ok(OnInconsistency::Record),
+            "reject" => Ok(OnInconsistency::Reject),
+            _ => Err(format!(
+                "unknown inconsistency handling {:?}, expected record or reject",
+                value
+            )),
+        }
+    }
+}
+
+// One invariant that did not hold: `expected` is derived from the
+// intervals, `actual` is what meta says
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Issue {
+    pub check: &'static str,
+    pub expected: u128,
+    pub actual: u128,
+}
+
+// A meta total and how to read its per-interval counterpart
+type SumCheck<T> = (&'static str, u128, fn(&T) -> u128);
+
+#[derive(Default)]
+struct Checks(Vec<Issue>);
+
+impl Checks {
+    fn expect(&mut self, check: &'static str, expected: u128, actual: u128) {
+        if expected != actual {
+            self.0.push(Issue {
+                check,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    // meta covers exactly the window of its intervals
+    fn window(&mut self, meta: (u128, u128), first: Option<u128>, last: Option<u128>) {
+        if let (Some(first), Some(last)) = (first, last) {
+            self.expect("meta.startTime = first(intervals.startTime)", first, meta.0);
+            self.expect("meta.endTime = last(intervals.endTime)", last, meta.1);
+        }
+    }
+
+    fn sums<T>(&mut self, intervals: &[T], sums: &[SumCheck<T>]) {
+        for (check, actual, field) in sums {
+            let expected = intervals.iter().fold(0_u128, |total, interval| {
+                total.saturating_add(field(interval))
+            });
+            self.expect(check, expected, *actual);
+        }
+    }
+}
+
+pub fn check_runepool(data: &rune_pool::RunePoolIntervalsInt) -> Vec<Issue> {
+    let mut checks = Checks::default();
+    let (first, last) = (data.intervals.first(), data.intervals.last());
+    checks.window(
+        (data.meta.startTime, data.meta.endTime),
+        first.map(|i| i.startTime),
+        last.map(|i| i.endTime),
+    );
+    if let Some(last) = last {
+        checks.expect(
+            "meta.endUnits = last(intervals.units)",
+            last.units,
+            data.meta.endUnits,
+        );
+        checks.expect(
+            "meta.endCount = last(intervals.count)",
+            last.count,
+            data.meta.endCount,
+        );
+    }
+    checks.0
+}
+
+pub fn check_depths(data: &depth_data::RootDepthDetails) -> Vec<Issue> {
+    let mut checks = Checks::default();
+    let meta = &data.meta;
+    let (first, last) = (data.intervals.first(), data.intervals.last());
+    checks.window(
+        (meta.startTime, meta.endTime),
+        first.map(|i| i.startTime),
+        last.map(|i| i.endTime),
+    );
+    if let Some(last) = last {
+        for (check, expected, actual) in [
+            (
+                "meta.endAssetDepth = last(intervals.assetDepth)",
+                last.assetDepth,
+                meta.endAssetDepth,
+            ),
+            (
+                "meta.endRuneDepth = last(intervals.runeDepth)",
+                last.runeDepth,
+                meta.endRuneDepth,
+            ),
+            (
+                "meta.endLPUnits = last(intervals.liquidityUnits)",
+                last.liquidityUnits,
+                meta.endLPUnits,
+            ),
+            (
+                "meta.endMemberCount = last(intervals.membersCount)",
+                last.membersCount,
+                meta.endMemberCount,
+            ),
+            (
+                "meta.endSynthUnits = last(intervals.synthUnits)",
+                last.synthUnits,
+                meta.endSynthUnits,
+            ),
+        ] {
+            checks.expect(check, expected, actual);
+        }
+    }
+    checks.0
+}
+
+pub fn check_earnings(data: &earning_history::RootEarnDetails) -> Vec<Issue> {
+    let mut checks = Checks::default();
+    let meta = &data.meta;
+    checks.window(
+        (meta.startTime, meta.endTime),
+        data.intervals.first().map(|i| i.startTime),
+        data.intervals.last().map(|i| i.endTime),
+    );
+    checks.sums(
+        &data.intervals,
+        &[
+            (
+                "meta.liquidityFees = sum(intervals.liquidityFees)",
+                meta.liquidityFees,
+                |i| i.liquidityFees,
+            ),
+            (
+                "meta.blockRewards = sum(intervals.blockRewards)",
+                meta.blockRewards,
+                |i| i.blockRewards,
+            ),
+            (
+                "meta.earnings = sum(intervals.earnings)",
+                meta.earnings,
+                |i| i.earnings,
+            ),
+            (
+                "meta.bondingEarnings = sum(intervals.bondingEarnings)",
+                meta.bondingEarnings,
+                |i| i.bondingEarnings,
+            ),
+            (
+                "meta.liquidityEarnings = sum(intervals.liquidityEarnings)",
+                meta.liquidityEarnings,
+                |i| i.liquidityEarnings,
+            ),
+        ],
+    );
+    checks.0
+}
+
+pub fn check_swaps(data: &swap_history::RootSwapDetails) -> Vec<Issue> {
+    let mut checks = Checks::default();
+    let meta = &data.meta;
+    checks.window(
+        (meta.startTime, meta.endTime),
+        data.intervals.first().map(|i| i.startTime),
+        data.intervals.last().map(|i| i.endTime),
+    );
+    checks.sums(
+        &data.intervals,
+        &[
+            (
+                "meta.toAssetCount = sum(intervals.toAssetCount)",
+                meta.toAssetCount,
+                |i| i.toAssetCount,
+            ),
+            (
+                "meta.toRuneCount = sum(intervals.toRuneCount)",
+                meta.toRuneCount,
+                |i| i.toRuneCount,
+            ),
+            (
+                "meta.toTradeCount = sum(intervals.toTradeCount)",
+                meta.toTradeCount,
+                |i| i.toTradeCount,
+            ),
+            (
+                "meta.fromTradeCount = sum(intervals.fromTradeCount)",
+                meta.fromTradeCount,
+                |i| i.fromTradeCount,
+            ),
+            (
+                "meta.synthMintCount = sum(intervals.synthMintCount)",
+                meta.synthMintCount,
+                |i| i.synthMintCount,
+            ),
+            (
+                "meta.synthRedeemCount = sum(intervals.synthRedeemCount)",
+                meta.synthRedeemCount,
+                |i| i.synthRedeemCount,
+            ),
+            (
+                "meta.totalCount = sum(intervals.totalCount)",
+                meta.totalCount,
+                |i| i.totalCount,
+            ),
+            (
+                "meta.totalVolume = sum(intervals.totalVolume)",
+                meta.totalVolume,
+                |i| i.totalVolume,
+            ),
+        ],
+    );
+    checks.0
+}
+
+// Starts the transaction a payload is stored in, archiving its raw response
+// and recording its issues first. A rejected payload stops there: the
+// archive and the issues are committed without any rows, and the rejection
+// comes back as the error.
+pub async fn archive(
+    db: &PgPool,
+    raw: &RawResponse,
+    dataset: Dataset,
+    pool: Option<&str>,
+    issues: &[Issue],
+    on_inconsistency: OnInconsistency,
+) -> Result<(Transaction<'static, Postgres>, i64), AppError> {
+    let rejected = on_inconsistency == OnInconsistency::Reject && !issues.is_empty();
+    let mut tx = db.begin().await?;
+    let raw_id = insert_raw_response(raw, &mut *tx).await?;
+
+    for issue in issues {
+        sqlx::query(
+            r#"
+            INSERT INTO data_quality_issues
+                (dataset, pool, raw_response_id, check_name, expected, actual, rejected)
+            VALUES ($1, $2, $3, $4, $5::numeric, $6::numeric, $7)
+            "#,
+        )
+        .bind(dataset.as_str())
+        .bind(pool)
+        .bind(raw_id)
+        .bind(issue.check)
+        .bind(issue.expected.to_string())
+        .bind(issue.actual.to_string())
+        .bind(rejected)
+        .execute(&mut *tx)
+        .await?;
+    }
+    if let Some(issue) = issues.first() {
+        eprintln!(
+            "{} from {} failed {} consistency checks, e.g. {} ({} vs {})",
+            dataset.series_label(pool),
+            raw.url,
+            issues.len(),
+            issue.check,
+            issue.expected,
+            issue.actual
+        );
+    }
+
+    if rejected {
+        tx.commit().await?;
+        return Err(AppError::Inconsistent(format!(
+            "{} failed {} consistency checks (raw response {})",
+            dataset.series_label(pool),
+            issues.len(),
+            raw_id
+        )));
+    }
+    Ok((tx, raw_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch_data_from_midgard::fixtures::ResponseSource;
+    use crate::fetch_data_from_midgard::midgard_client::{HistoryParams, Interval, MidgardClient};
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+    use crate::ingestion_pipeline::ingest::{ingest_page, IntervalFilter};
+    use crate::mock_midgard::server::{spawn, Fault, MockOptions};
+
+    #[test]
+    fn compares_swap_meta_with_interval_sums() {
+        let mut data: swap_history::RootSwapDetails = serde_json::from_str(include_str!(
+            "../../fixtures/swaps_BTC.BTC_hour_to1714532400_count3.json"
+        ))
+        .unwrap();
+        assert_eq!(check_swaps(&data), Vec::new());
+
+        data.meta.totalCount += 1;
+        data.intervals.pop();
+        let issues = check_swaps(&data);
+        let checks: Vec<_> = issues.iter().map(|issue| issue.check).collect();
+        assert_eq!(checks[0], "meta.endTime = last(intervals.endTime)");
+        assert!(checks.contains(&"meta.totalCount = sum(intervals.totalCount)"));
+        assert!(issues.iter().all(|issue| issue.expected != issue.actual));
+    }
+
+    // `fixtures/inconsistent/` holds the recorded BTC.BTC swaps with
+    // meta.totalCount lowered by 100 by hand, so exactly one check fails
+    #[test]
+    fn reports_the_one_total_that_does_not_add_up() {
+        let data: swap_history::RootSwapDetails = serde_json::from_str(include_str!(
+            "../../fixtures/inconsistent/swaps_BTC.BTC_hour_to1714532400_count3.json"
+        ))
+        .unwrap();
+        assert_eq!(
+            check_swaps(&data),
+            vec![Issue {
+                check: "meta.totalCount = sum(intervals.totalCount)",
+                expected: 1_307,
+                actual: 1_207,
+            }]
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn records_or_rejects_an_inconsistent_recording(db: PgPool) {
+        let midgard = MidgardClient::with_policy(
+            vec!["http://127.0.0.1:1".to_string()],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        )
+        .with_source(ResponseSource::Replay(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/inconsistent").into(),
+        ));
+        let params = HistoryParams {
+            count: Some(3),
+            to: Some(1_714_532_400),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        let ingest = |on_inconsistency| {
+            ingest_page(
+                &midgard,
+                &db,
+                Dataset::Swaps,
+                Some("BTC.BTC"),
+                &params,
+                IntervalFilter::default(),
+                on_inconsistency,
+            )
+        };
+        let stored = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM swap_data_rune_pool_interval")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+        };
+
+        let error = ingest(OnInconsistency::Reject).await.unwrap_err();
+        assert!(matches!(error, AppError::Inconsistent(_)), "{:?}", error);
+        assert_eq!(stored().await, 0);
+
+        let page = ingest(OnInconsistency::Record).await.unwrap();
+        assert_eq!(page.rows.inserted, 3);
+        assert_eq!(stored().await, 3);
+
+        let issues: Vec<(String, String, String, bool)> = sqlx::query_as(
+            "SELECT check_name, expected::text, actual::text, rejected \
+             FROM data_quality_issues ORDER BY rejected DESC",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        let check = "meta.totalCount = sum(intervals.totalCount)".to_string();
+        assert_eq!(
+            issues,
+            [
+                (check.clone(), "1307".to_string(), "1207".to_string(), true),
+                (check, "1307".to_string(), "1207".to_string(), false),
+            ]
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn records_issues_and_optionally_rejects_the_payload(db: PgPool) {
+        // the first interval's numbers read as 0, so meta no longer adds up
+        let base_url = spawn(MockOptions {
+            fault: Some(Fault::EmptyStrings),
+            now: Some(1_714_521_600),
+            ..MockOptions::default()
+        })
+        .await;
+        let midgard = MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        );
+        let params = HistoryParams {
+            count: Some(4),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        let ingest = |on_inconsistency| {
+            ingest_page(
+                &midgard,
+                &db,
+                Dataset::Swaps,
+                Some("BTC.BTC"),
+                &params,
+                IntervalFilter::default(),
+                on_inconsistency,
+            )
+        };
+        let stored = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM swap_data_rune_pool_interval")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+        };
+
+        let error = ingest(OnInconsistency::Reject).await.unwrap_err();
+        assert!(matches!(error, AppError::Inconsistent(_)), "{:?}", error);
+        assert_eq!(stored().await, 0);
+
+        let page = ingest(OnInconsistency::Record).await.unwrap();
+        assert_eq!(page.rows.inserted, 4);
+        assert_eq!(stored().await, 4);
+
+        let issues: Vec<(String, String, bool, i64)> = sqlx::query_as(
+            r#"
+            SELECT check_name, pool, rejected, COUNT(DISTINCT raw_response_id)
+            FROM data_quality_issues
+            GROUP BY check_name, pool, rejected
+            ORDER BY check_name, rejected
+            "#,
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert!(issues.contains(&(
+            "meta.totalCount = sum(intervals.totalCount)".to_string(),
+            "BTC.BTC".to_string(),
+            true,
+            1
+        )));
+        assert!(issues.contains(&(
+            "meta.totalCount = sum(intervals.totalCount)".to_string(),
+            "BTC.BTC".to_string(),
+            false,
+            1
+        )));
+    }
+}
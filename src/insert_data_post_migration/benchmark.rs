@@ -0,0 +1,49 @@
+// Shared by the insert scripts' ignored benchmarks, run with
+// `cargo test insert_benchmark -- --ignored --nocapture`
+use crate::fetch_data_from_midgard::midgard_client::Interval;
+use crate::mock_midgard::series::{self, MAX_BUCKETS};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::future::Future;
+use std::time::Instant;
+
+// 2024-05-02 00:00 UTC
+const NOW: i64 = 1_714_608_000;
+
+// A backfill-sized page: Midgard's largest, of hourly intervals
+pub fn page<T: DeserializeOwned>(series: impl Fn(&series::Window) -> Value) -> T {
+    let window = series::window(Interval::Hour, Some(MAX_BUCKETS as u32), None, None, NOW).unwrap();
+    // the deserializers borrow their strings, so go through text
+    serde_json::from_str(&series(&window).to_string()).unwrap()
+}
+
+// Times the row-by-row write path, empties `table`, then times the bulk
+// one, and prints both with the speedup
+pub async fn compare(
+    what: &str,
+    pool: &PgPool,
+    table: &str,
+    row_by_row: impl Future<Output = ()>,
+    bulk: impl Future<Output = ()>,
+) {
+    let started = Instant::now();
+    row_by_row.await;
+    let row_by_row = started.elapsed();
+    sqlx::query(&format!("TRUNCATE {} CASCADE", table))
+        .execute(pool)
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    bulk.await;
+    let bulk = started.elapsed();
+
+    std::println!(
+        "{}: row-by-row {:?}, bulk {:?} ({:.1}x)",
+        what,
+        row_by_row,
+        bulk,
+        row_by_row.as_secs_f64() / bulk.as_secs_f64()
+    );
+}
@@ -1,234 +1,235 @@
 use crate::data_structs::depth_data::RootDepthDetails;
-use sqlx::PgPool;
-
-pub async fn insert_data(pool: &PgPool, data: RootDepthDetails) -> Result<(), sqlx::Error> {
-    let meta_start_time: i64 = data.meta.startTime.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartTime {} too large for i64",
-            data.meta.startTime
-        ))
-    })?;
-
-    let meta_end_time: i64 = data.meta.endTime.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("EndTime {} too large for i64", data.meta.endTime))
-    })?;
-
-    let meta_price_shift_loss: f64 = data.meta.priceShiftLoss.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "PriceShiftLoss {} too large for f64",
-            data.meta.priceShiftLoss
-        ))
-    })?;
-
-    let meta_luvi_increase: f64 = data.meta.luviIncrease.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "LuviIncrease {} too large for f64",
-            data.meta.luviIncrease
-        ))
-    })?;
-
-    let meta_start_asset_depth: i64 = data.meta.startAssetDepth.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartAssetDepth {} too large for i64",
-            data.meta.startAssetDepth
-        ))
-    })?;
-
-    let meta_start_rune_depth: i64 = data.meta.startRuneDepth.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartRuneDepth {} too large for i64",
-            data.meta.startRuneDepth
-        ))
-    })?;
-
-    let meta_start_lp_units: i64 = data.meta.startLPUnits.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartLPUnits {} too large for i64",
-            data.meta.startLPUnits
-        ))
-    })?;
-
-    let meta_start_member_count: i64 = data.meta.startMemberCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartMemberCount {} too large for i64",
-            data.meta.startMemberCount
-        ))
-    })?;
-
-    let meta_start_synth_units: i64 = data.meta.startSynthUnits.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartSynthUnits {} too large for i64",
-            data.meta.startSynthUnits
-        ))
-    })?;
-
-    let meta_end_asset_depth: i64 = data.meta.endAssetDepth.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "EndAssetDepth {} too large for i64",
-            data.meta.endAssetDepth
-        ))
-    })?;
-
-    let meta_end_rune_depth: i64 = data.meta.endRuneDepth.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "EndRuneDepth {} too large for i64",
-            data.meta.endRuneDepth
-        ))
-    })?;
-
-    let meta_end_lp_units: i64 = data.meta.endLPUnits.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "EndLPUnits {} too large for i64",
-            data.meta.endLPUnits
-        ))
-    })?;
-
-    let meta_end_member_count: i64 = data.meta.endMemberCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "EndMemberCount {} too large for i64",
-            data.meta.endMemberCount
-        ))
-    })?;
+use crate::error::AppError;
+use crate::insert_data_post_migration::Upserted;
+use bigdecimal::BigDecimal;
+use sqlx::{Acquire, Postgres};
+
+// `pool_name` is the Midgard pool the history was fetched for, e.g. BTC.BTC,
+// and `raw_response_id` the archived response `data` was decoded from
+pub async fn insert_data<'a, A>(
+    db: A,
+    pool_name: &str,
+    data: RootDepthDetails,
+    raw_response_id: Option<i64>,
+) -> Result<Upserted, AppError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    let mut tx = db.begin().await?;
+
+    let meta_start_time: i64 = data
+        .meta
+        .startTime
+        .try_into()
+        .map_err(|_| AppError::Overflow {
+            field: "StartTime",
+            value: data.meta.startTime,
+        })?;
 
-    let meta_end_synth_units: i64 = data.meta.endSynthUnits.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "EndSynthUnits {} too large for i64",
-            data.meta.endSynthUnits
-        ))
-    })?;
+    let meta_end_time: i64 = data
+        .meta
+        .endTime
+        .try_into()
+        .map_err(|_| AppError::Overflow {
+            field: "EndTime",
+            value: data.meta.endTime,
+        })?;
 
     sqlx::query(
         r#"
         INSERT INTO Rune_Pool_Depth_Meta (
-    startTime, endTime, priceShiftLoss, luviIncrease,
+    pool, startTime, endTime, priceShiftLoss, luviIncrease,
     startAssetDepth, startRuneDepth, startLPUnits, startMemberCount, startSynthUnits,
     endAssetDepth, endRuneDepth, endLPUnits, endMemberCount, endSynthUnits
-) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9,$10, $11, $12, $13, $14)
+) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9,$10, $11, $12, $13, $14, $15)
+ON CONFLICT (pool, startTime, endTime) DO UPDATE SET
+    priceShiftLoss = EXCLUDED.priceShiftLoss,
+    luviIncrease = EXCLUDED.luviIncrease,
+    startAssetDepth = EXCLUDED.startAssetDepth,
+    startRuneDepth = EXCLUDED.startRuneDepth,
+    startLPUnits = EXCLUDED.startLPUnits,
+    startMemberCount = EXCLUDED.startMemberCount,
+    startSynthUnits = EXCLUDED.startSynthUnits,
+    endAssetDepth = EXCLUDED.endAssetDepth,
+    endRuneDepth = EXCLUDED.endRuneDepth,
+    endLPUnits = EXCLUDED.endLPUnits,
+    endMemberCount = EXCLUDED.endMemberCount,
+    endSynthUnits = EXCLUDED.endSynthUnits
     "#,
     )
+    .bind(pool_name)
     .bind(meta_start_time)
     .bind(meta_end_time)
-    .bind(meta_price_shift_loss)
-    .bind(meta_luvi_increase)
-    .bind(meta_start_asset_depth)
-    .bind(meta_start_rune_depth)
-    .bind(meta_start_lp_units)
-    .bind(meta_start_member_count)
-    .bind(meta_start_synth_units)
-    .bind(meta_end_asset_depth)
-    .bind(meta_end_rune_depth)
-    .bind(meta_end_lp_units)
-    .bind(meta_end_member_count)
-    .bind(meta_end_synth_units)
-    .execute(pool)
+    .bind(&data.meta.priceShiftLoss)
+    .bind(&data.meta.luviIncrease)
+    .bind(BigDecimal::from(data.meta.startAssetDepth))
+    .bind(BigDecimal::from(data.meta.startRuneDepth))
+    .bind(BigDecimal::from(data.meta.startLPUnits))
+    .bind(BigDecimal::from(data.meta.startMemberCount))
+    .bind(BigDecimal::from(data.meta.startSynthUnits))
+    .bind(BigDecimal::from(data.meta.endAssetDepth))
+    .bind(BigDecimal::from(data.meta.endRuneDepth))
+    .bind(BigDecimal::from(data.meta.endLPUnits))
+    .bind(BigDecimal::from(data.meta.endMemberCount))
+    .bind(BigDecimal::from(data.meta.endSynthUnits))
+    .execute(&mut *tx)
     .await?;
 
+    // Collect the intervals column-wise so the whole page is one UNNEST upsert
+    let mut start_times = Vec::with_capacity(data.intervals.len());
+    let mut end_times = Vec::with_capacity(data.intervals.len());
+    let mut asset_depths = Vec::with_capacity(data.intervals.len());
+    let mut rune_depths = Vec::with_capacity(data.intervals.len());
+    let mut asset_prices = Vec::with_capacity(data.intervals.len());
+    let mut asset_prices_usd = Vec::with_capacity(data.intervals.len());
+    let mut liquidity_units = Vec::with_capacity(data.intervals.len());
+    let mut members_counts = Vec::with_capacity(data.intervals.len());
+    let mut synth_units = Vec::with_capacity(data.intervals.len());
+    let mut synth_supplies = Vec::with_capacity(data.intervals.len());
+    let mut units = Vec::with_capacity(data.intervals.len());
+    let mut luvis = Vec::with_capacity(data.intervals.len());
     for interval in &data.intervals {
-        let start_time: i64 = interval.startTime.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval StartTime {} too large for i64",
-                interval.startTime
-            ))
-        })?;
-
-        let end_time: i64 = interval.endTime.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval EndTime {} too large for i64",
-                interval.endTime
-            ))
-        })?;
-
-        let asset_depth: i64 = interval.assetDepth.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval AssetDepth {} too large for i64",
-                interval.assetDepth
-            ))
-        })?;
-
-        let rune_depth: i64 = interval.runeDepth.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval RuneDepth {} too large for i64",
-                interval.runeDepth
-            ))
-        })?;
-
-        let asset_price: f64 = interval.assetPrice.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval AssetPrice {} too large for f64",
-                interval.assetPrice
-            ))
-        })?;
-
-        let asset_price_usd: f64 = interval.assetPriceUSD.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval AssetPriceUSD {} too large for f64",
-                interval.assetPriceUSD
-            ))
-        })?;
-
-        let liquidity_units: i64 = interval.liquidityUnits.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval LiquidityUnits {} too large for i64",
-                interval.liquidityUnits
-            ))
-        })?;
-
-        let members_count: i64 = interval.membersCount.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval MembersCount {} too large for i64",
-                interval.membersCount
-            ))
-        })?;
-
-        let synth_units: i64 = interval.synthUnits.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval SynthUnits {} too large for i64",
-                interval.synthUnits
-            ))
-        })?;
+        let start_time: i64 = interval
+            .startTime
+            .try_into()
+            .map_err(|_| AppError::Overflow {
+                field: "Interval StartTime",
+                value: interval.startTime,
+            })?;
+
+        let end_time: i64 = interval
+            .endTime
+            .try_into()
+            .map_err(|_| AppError::Overflow {
+                field: "Interval EndTime",
+                value: interval.endTime,
+            })?;
+
+        start_times.push(start_time);
+        end_times.push(end_time);
+        asset_depths.push(BigDecimal::from(interval.assetDepth));
+        rune_depths.push(BigDecimal::from(interval.runeDepth));
+        asset_prices.push(interval.assetPrice.clone());
+        asset_prices_usd.push(interval.assetPriceUSD.clone());
+        liquidity_units.push(BigDecimal::from(interval.liquidityUnits));
+        members_counts.push(BigDecimal::from(interval.membersCount));
+        synth_units.push(BigDecimal::from(interval.synthUnits));
+        synth_supplies.push(BigDecimal::from(interval.synthSupply));
+        units.push(BigDecimal::from(interval.units));
+        luvis.push(interval.luvi.clone());
+    }
 
-        let synth_supply: i64 = interval.synthSupply.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval SynthSupply {} too large for i64",
-                interval.synthSupply
-            ))
-        })?;
+    let fresh: Vec<bool> = sqlx::query_scalar(
+        r#"
+        INSERT INTO Rune_Pool_Depth_Intervals (
+    pool, startTime, endTime, assetDepth, runeDepth, assetPrice, assetPriceUSD,
+    liquidityUnits, membersCount, synthUnits, synthSupply, units, luvi, raw_response_id)
+    SELECT $1, t.*, $14 FROM UNNEST(
+        $2::bigint[], $3::bigint[], $4::numeric[], $5::numeric[], $6::numeric[], $7::numeric[],
+        $8::numeric[], $9::numeric[], $10::numeric[], $11::numeric[], $12::numeric[], $13::numeric[]
+    ) AS t
+    ON CONFLICT (pool, startTime, endTime) DO UPDATE SET
+        assetDepth = EXCLUDED.assetDepth,
+        runeDepth = EXCLUDED.runeDepth,
+        assetPrice = EXCLUDED.assetPrice,
+        assetPriceUSD = EXCLUDED.assetPriceUSD,
+        liquidityUnits = EXCLUDED.liquidityUnits,
+        membersCount = EXCLUDED.membersCount,
+        synthUnits = EXCLUDED.synthUnits,
+        synthSupply = EXCLUDED.synthSupply,
+        units = EXCLUDED.units,
+        luvi = EXCLUDED.luvi,
+        raw_response_id = EXCLUDED.raw_response_id
+    RETURNING (xmax = 0)
+    "#,
+    )
+    .bind(pool_name)
+    .bind(start_times)
+    .bind(end_times)
+    .bind(asset_depths)
+    .bind(rune_depths)
+    .bind(asset_prices)
+    .bind(asset_prices_usd)
+    .bind(liquidity_units)
+    .bind(members_counts)
+    .bind(synth_units)
+    .bind(synth_supplies)
+    .bind(units)
+    .bind(luvis)
+    .bind(raw_response_id)
+    .fetch_all(&mut *tx)
+    .await?;
 
-        let units: i64 = interval.units.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval Units {} too large for i64",
-                interval.units
-            ))
-        })?;
+    std::println!(
+        "{} depth intervals inserted for {}",
+        data.intervals.len(),
+        pool_name
+    );
+    tx.commit().await?;
+    Ok(Upserted::from_flags(&fresh))
+}
 
-        let luvi: f64 = interval.luvi.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!("Interval Luvi {} too large for f64", interval.luvi))
-        })?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::insert_data_post_migration::benchmark;
+    use crate::mock_midgard::series;
+    use sqlx::PgPool;
+
+    // The pre-bulk write path: one round-trip per interval
+    async fn insert_row_by_row(pool_name: &str, data: &RootDepthDetails, db: &PgPool) {
+        let mut tx = db.begin().await.unwrap();
+        for interval in &data.intervals {
+            sqlx::query(
+                r#"
+                INSERT INTO Rune_Pool_Depth_Intervals (
+                    pool, startTime, endTime, assetDepth, runeDepth, assetPrice, assetPriceUSD,
+                    liquidityUnits, membersCount, synthUnits, synthSupply, units, luvi
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+                ON CONFLICT (pool, startTime, endTime) DO UPDATE SET units = EXCLUDED.units
+                "#,
+            )
+            .bind(pool_name)
+            .bind(interval.startTime as i64)
+            .bind(interval.endTime as i64)
+            .bind(BigDecimal::from(interval.assetDepth))
+            .bind(BigDecimal::from(interval.runeDepth))
+            .bind(&interval.assetPrice)
+            .bind(&interval.assetPriceUSD)
+            .bind(BigDecimal::from(interval.liquidityUnits))
+            .bind(BigDecimal::from(interval.membersCount))
+            .bind(BigDecimal::from(interval.synthUnits))
+            .bind(BigDecimal::from(interval.synthSupply))
+            .bind(BigDecimal::from(interval.units))
+            .bind(&interval.luvi)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        }
+        tx.commit().await.unwrap();
+    }
 
-        sqlx::query(
-            r#"
-        INSERT INTO Rune_Pool_Depth_Intervals (
-    startTime, endTime, assetDepth, runeDepth, assetPrice, assetPriceUSD,
-    liquidityUnits, membersCount, synthUnits, synthSupply, units, luvi) 
-    VALUES ($1, $2, $3, $4, $5,$6, $7, $8, $9, $10,$11, $12)
-    "#,
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore]
+    async fn depth_insert_benchmark(pool: PgPool) {
+        let depths = |window: &series::Window| series::depths("BTC.BTC", window);
+        let slow: RootDepthDetails = benchmark::page(depths);
+        let fast: RootDepthDetails = benchmark::page(depths);
+        benchmark::compare(
+            "400 depth intervals",
+            &pool,
+            "Rune_Pool_Depth_Intervals",
+            insert_row_by_row("BTC.BTC", &slow, &pool),
+            async {
+                insert_data(&pool, "BTC.BTC", fast, None).await.unwrap();
+            },
         )
-        .bind(start_time)
-        .bind(end_time)
-        .bind(asset_depth)
-        .bind(rune_depth)
-        .bind(asset_price)
-        .bind(asset_price_usd)
-        .bind(liquidity_units)
-        .bind(members_count)
-        .bind(synth_units)
-        .bind(synth_supply)
-        .bind(units)
-        .bind(luvi)
-        .execute(pool)
-        .await?;
+        .await;
 
-        std::println!("Interval inserted successfully!");
+        let (stored,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM Rune_Pool_Depth_Intervals")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, 400);
     }
-    Ok(())
 }
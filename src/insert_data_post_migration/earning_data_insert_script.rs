@@ -1,103 +1,82 @@
 use crate::data_structs::earning_history::{PoolData, RunePoolInterval, RunePoolMeta};
-use sqlx;
-
-pub async fn insert_pool_data(
-    pool_data: &PoolData,
-    interval_id: i32,  // Add interval_id parameter
-    pool: &sqlx::PgPool,
-) -> Result<(), sqlx::Error> {
-    // Helper function to convert f64 to i64 with range checking
-    fn f64_to_i64(value: f64, field_name: &str) -> Result<i64, sqlx::Error> {
-        if value.is_finite() && value >= i64::MIN as f64 && value <= i64::MAX as f64 {
-            Ok(value.round() as i64)
-        } else {
-            Err(sqlx::Error::Protocol(format!(
-                "{} value {} is out of range for i64",
-                field_name, value
-            )))
-        }
-    }
+use crate::error::AppError;
+use crate::insert_data_post_migration::Upserted;
+use bigdecimal::BigDecimal;
+use sqlx::{Acquire, Executor, Postgres};
+use std::collections::HashMap;
 
-    // Convert values
-    let asset_liquidity_fees = f64_to_i64(pool_data.assetLiquidityFees, "assetLiquidityFees")?;
-    let rune_liquidity_fees = f64_to_i64(pool_data.runeLiquidityFees, "runeLiquidityFees")?;
-    let total_liquidity_fees_rune = f64_to_i64(pool_data.totalLiquidityFeesRune, "totalLiquidityFeesRune")?;
-    let saver_earning = f64_to_i64(pool_data.saverEarning, "saverEarning")?;
-    let rewards = f64_to_i64(pool_data.rewards, "rewards")?;
-    let earnings = f64_to_i64(pool_data.earnings, "earnings")?;
+// Upserts the per-pool breakdown of several intervals in one statement;
+// each row pairs a pool entry with the id of its parent interval
+pub async fn insert_pool_data<'e, E>(rows: &[(i32, &PoolData)], db: E) -> Result<(), AppError>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let mut interval_ids = Vec::with_capacity(rows.len());
+    let mut pools = Vec::with_capacity(rows.len());
+    let mut asset_liquidity_fees = Vec::with_capacity(rows.len());
+    let mut rune_liquidity_fees = Vec::with_capacity(rows.len());
+    let mut total_liquidity_fees_rune = Vec::with_capacity(rows.len());
+    let mut saver_earnings = Vec::with_capacity(rows.len());
+    let mut rewards = Vec::with_capacity(rows.len());
+    let mut earnings = Vec::with_capacity(rows.len());
+    for (interval_id, pool_data) in rows {
+        interval_ids.push(*interval_id);
+        pools.push(pool_data.pool.clone());
+        asset_liquidity_fees.push(BigDecimal::from(pool_data.assetLiquidityFees));
+        rune_liquidity_fees.push(BigDecimal::from(pool_data.runeLiquidityFees));
+        total_liquidity_fees_rune.push(BigDecimal::from(pool_data.totalLiquidityFeesRune));
+        saver_earnings.push(BigDecimal::from(pool_data.saverEarning));
+        rewards.push(BigDecimal::from(pool_data.rewards));
+        earnings.push(BigDecimal::from(pool_data.earnings));
+    }
 
-    // Insert into the database with interval_id
     sqlx::query!(
         r#"
         INSERT INTO earning_data_pool_data (
             interval_id, pool, asset_liquidity_fees, rune_liquidity_fees,
             total_liquidity_fees_rune, saver_earning, rewards, earnings
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        )
+        SELECT * FROM UNNEST(
+            $1::int[], $2::text[], $3::numeric[], $4::numeric[],
+            $5::numeric[], $6::numeric[], $7::numeric[], $8::numeric[]
+        )
+        ON CONFLICT (interval_id, pool) DO UPDATE SET
+            asset_liquidity_fees = EXCLUDED.asset_liquidity_fees,
+            rune_liquidity_fees = EXCLUDED.rune_liquidity_fees,
+            total_liquidity_fees_rune = EXCLUDED.total_liquidity_fees_rune,
+            saver_earning = EXCLUDED.saver_earning,
+            rewards = EXCLUDED.rewards,
+            earnings = EXCLUDED.earnings
         "#,
-        interval_id,
-        pool_data.pool,
-        asset_liquidity_fees,
-        rune_liquidity_fees,
-        total_liquidity_fees_rune,
-        saver_earning,
-        rewards,
-        earnings
+        &interval_ids,
+        &pools,
+        &asset_liquidity_fees,
+        &rune_liquidity_fees,
+        &total_liquidity_fees_rune,
+        &saver_earnings,
+        &rewards,
+        &earnings
     )
-    .execute(pool)
+    .execute(db)
     .await?;
 
     Ok(())
 }
 
-pub async fn insert_rune_pool_meta(
-    meta: &RunePoolMeta,
-    pool: &sqlx::PgPool,
-) -> Result<(), sqlx::Error> {
+pub async fn insert_rune_pool_meta<'e, E>(meta: &RunePoolMeta, db: E) -> Result<(), AppError>
+where
+    E: Executor<'e, Database = Postgres>,
+{
     // Convert meta.startTime to i64 with error handling
-    let meta_start_time: i64 = meta.startTime.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("startTime {} too large for i64", meta.startTime))
+    let meta_start_time: i64 = meta.startTime.try_into().map_err(|_| AppError::Overflow {
+        field: "startTime",
+        value: meta.startTime,
     })?;
 
     // Convert meta.endTime to i64 with error handling
-    let meta_end_time: i64 = meta.endTime.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("endTime {} too large for i64", meta.endTime))
-    })?;
-
-    // Convert meta.liquidityFees to i64 with error handling
-    let meta_liquidity_fees: i64 = meta.liquidityFees.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "liquidityFees {} too large for i64",
-            meta.liquidityFees
-        ))
-    })?;
-
-    // Convert meta.blockRewards to i64 with error handling
-    let meta_block_rewards: i64 = meta.blockRewards.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "blockRewards {} too large for i64",
-            meta.blockRewards
-        ))
-    })?;
-
-    // Convert meta.earnings to i64 with error handling
-    let meta_earnings: i64 = meta.earnings.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("earnings {} too large for i64", meta.earnings))
-    })?;
-
-    // Convert meta.bondingEarnings to i64 with error handling
-    let meta_bonding_earnings: i64 = meta.bondingEarnings.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "bondingEarnings {} too large for i64",
-            meta.bondingEarnings
-        ))
-    })?;
-
-    // Convert meta.liquidityEarnings to i64 with error handling
-    let meta_liquidity_earnings: i64 = meta.liquidityEarnings.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "liquidityEarnings {} too large for i64",
-            meta.liquidityEarnings
-        ))
+    let meta_end_time: i64 = meta.endTime.try_into().map_err(|_| AppError::Overflow {
+        field: "endTime",
+        value: meta.endTime,
     })?;
 
     // Use f64 directly for avgNodeCount and runePriceUSD as they do not need conversion
@@ -111,19 +90,27 @@ pub async fn insert_rune_pool_meta(
             start_time, end_time, liquidity_fees, block_rewards, earnings, bonding_earnings, 
             liquidity_earnings, avg_node_count, rune_price_usd
         ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (start_time, end_time) DO UPDATE SET
+            liquidity_fees = EXCLUDED.liquidity_fees,
+            block_rewards = EXCLUDED.block_rewards,
+            earnings = EXCLUDED.earnings,
+            bonding_earnings = EXCLUDED.bonding_earnings,
+            liquidity_earnings = EXCLUDED.liquidity_earnings,
+            avg_node_count = EXCLUDED.avg_node_count,
+            rune_price_usd = EXCLUDED.rune_price_usd
         RETURNING id
         "#,
     )
     .bind(meta_start_time)
     .bind(meta_end_time)
-    .bind(meta_liquidity_fees)
-    .bind(meta_block_rewards)
-    .bind(meta_earnings)
-    .bind(meta_bonding_earnings)
-    .bind(meta_liquidity_earnings)
+    .bind(BigDecimal::from(meta.liquidityFees))
+    .bind(BigDecimal::from(meta.blockRewards))
+    .bind(BigDecimal::from(meta.earnings))
+    .bind(BigDecimal::from(meta.bondingEarnings))
+    .bind(BigDecimal::from(meta.liquidityEarnings))
     .bind(avg_node_count)
     .bind(rune_price_usd)
-    .fetch_one(pool)
+    .fetch_one(db)
     .await?;
 
     // Log insertion success
@@ -132,38 +119,209 @@ pub async fn insert_rune_pool_meta(
     Ok(())
 }
 
-pub async fn insert_rune_pool_intervals(
+// An interval and its pool rows land together or not at all.
+// `raw_response_id` is the archived response the intervals were decoded from.
+pub async fn insert_rune_pool_intervals<'a, A>(
     intervals: &[RunePoolInterval],
-    pool: &sqlx::PgPool,
-) -> Result<(), sqlx::Error> {
+    raw_response_id: Option<i64>,
+    db: A,
+) -> Result<Upserted, AppError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    let mut tx = db.begin().await?;
+
+    let mut start_times = Vec::with_capacity(intervals.len());
+    let mut end_times = Vec::with_capacity(intervals.len());
+    let mut liquidity_fees = Vec::with_capacity(intervals.len());
+    let mut block_rewards = Vec::with_capacity(intervals.len());
+    let mut earnings = Vec::with_capacity(intervals.len());
+    let mut bonding_earnings = Vec::with_capacity(intervals.len());
+    let mut liquidity_earnings = Vec::with_capacity(intervals.len());
+    let mut avg_node_counts = Vec::with_capacity(intervals.len());
+    let mut rune_prices_usd = Vec::with_capacity(intervals.len());
     for interval in intervals {
-        // First insert the interval and get its id
-        let interval_id = sqlx::query!(
-            r#"
-            INSERT INTO earning_data_rune_pool_interval (
-                start_time, end_time, liquidity_fees, block_rewards, earnings, 
-                bonding_earnings, liquidity_earnings, avg_node_count, rune_price_usd
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-            RETURNING id
-            "#,
-            interval.startTime as i64,
-            interval.endTime as i64,
-            interval.liquidityFees as i64,
-            interval.blockRewards as i64,
-            interval.earnings as i64,
-            interval.bondingEarnings as i64,
-            interval.liquidityEarnings as i64,
-            interval.avgNodeCount,
-            interval.runePriceUSD
+        let start_time: i64 = interval
+            .startTime
+            .try_into()
+            .map_err(|_| AppError::Overflow {
+                field: "startTime",
+                value: interval.startTime,
+            })?;
+        let end_time: i64 = interval
+            .endTime
+            .try_into()
+            .map_err(|_| AppError::Overflow {
+                field: "endTime",
+                value: interval.endTime,
+            })?;
+
+        start_times.push(start_time);
+        end_times.push(end_time);
+        liquidity_fees.push(BigDecimal::from(interval.liquidityFees));
+        block_rewards.push(BigDecimal::from(interval.blockRewards));
+        earnings.push(BigDecimal::from(interval.earnings));
+        bonding_earnings.push(BigDecimal::from(interval.bondingEarnings));
+        liquidity_earnings.push(BigDecimal::from(interval.liquidityEarnings));
+        avg_node_counts.push(interval.avgNodeCount);
+        rune_prices_usd.push(interval.runePriceUSD);
+    }
+
+    // First upsert the intervals and get their ids, new or existing
+    let rows = sqlx::query!(
+        r#"
+        INSERT INTO earning_data_rune_pool_interval (
+            start_time, end_time, liquidity_fees, block_rewards, earnings,
+            bonding_earnings, liquidity_earnings, avg_node_count, rune_price_usd, raw_response_id
         )
-        .fetch_one(pool)
-        .await?
-        .id;
+        SELECT t.*, $10::bigint FROM UNNEST(
+            $1::bigint[], $2::bigint[], $3::numeric[], $4::numeric[], $5::numeric[],
+            $6::numeric[], $7::numeric[], $8::float8[], $9::float8[]
+        ) AS t
+        ON CONFLICT (start_time, end_time) DO UPDATE SET
+            liquidity_fees = EXCLUDED.liquidity_fees,
+            block_rewards = EXCLUDED.block_rewards,
+            earnings = EXCLUDED.earnings,
+            bonding_earnings = EXCLUDED.bonding_earnings,
+            liquidity_earnings = EXCLUDED.liquidity_earnings,
+            avg_node_count = EXCLUDED.avg_node_count,
+            rune_price_usd = EXCLUDED.rune_price_usd,
+            raw_response_id = EXCLUDED.raw_response_id
+        RETURNING id, start_time, (xmax = 0) AS "inserted!"
+        "#,
+        &start_times,
+        &end_times,
+        &liquidity_fees,
+        &block_rewards,
+        &earnings,
+        &bonding_earnings,
+        &liquidity_earnings,
+        &avg_node_counts,
+        &rune_prices_usd,
+        raw_response_id as Option<i64>
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let upserted = Upserted::from_flags(&rows.iter().map(|row| row.inserted).collect::<Vec<_>>());
+    let interval_ids: HashMap<i64, i32> = rows
+        .into_iter()
+        .map(|row| (row.start_time, row.id))
+        .collect();
+
+    // Then insert the pool data of every interval, matched back by start time
+    let mut pool_rows = Vec::new();
+    for (interval, start_time) in intervals.iter().zip(&start_times) {
+        let interval_id = interval_ids[start_time];
+        pool_rows.extend(
+            interval
+                .pools
+                .iter()
+                .map(|pool_data| (interval_id, pool_data)),
+        );
+    }
+    insert_pool_data(&pool_rows, &mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(upserted)
+}
 
-        // Then insert all pool data for this interval
-        for pool_data in &interval.pools {
-            insert_pool_data(pool_data, interval_id, pool).await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::insert_data_post_migration::benchmark;
+    use sqlx::PgPool;
+
+    // A backfill-sized page: 400 hourly intervals with 100 pools each
+    fn synthetic_intervals() -> Vec<RunePoolInterval> {
+        let intervals: Vec<_> = (0..400u64)
+            .map(|i| {
+                let pools: Vec<_> = (0..100)
+                    .map(|p| {
+                        serde_json::json!({
+                            "pool": format!("POOL.{}", p),
+                            "assetLiquidityFees": "123456789",
+                            "runeLiquidityFees": "987654321",
+                            "totalLiquidityFeesRune": "1111111110",
+                            "saverEarning": "0",
+                            "rewards": "-42",
+                            "earnings": "1111111068",
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "startTime": (1_700_000_000 + i * 3600).to_string(),
+                    "endTime": (1_700_003_600 + i * 3600).to_string(),
+                    "liquidityFees": "1000",
+                    "blockRewards": "2000",
+                    "earnings": "3000",
+                    "bondingEarnings": "1500",
+                    "liquidityEarnings": "1500",
+                    "avgNodeCount": "99.5",
+                    "runePriceUSD": "4.2",
+                    "pools": pools,
+                })
+            })
+            .collect();
+        // the deserializers borrow their strings, so go through text
+        serde_json::from_str(&serde_json::Value::Array(intervals).to_string()).unwrap()
+    }
+
+    // The pre-bulk write path: one round-trip per interval and per pool row
+    async fn insert_row_by_row(intervals: &[RunePoolInterval], db: &PgPool) {
+        let mut tx = db.begin().await.unwrap();
+        for interval in intervals {
+            let (interval_id,): (i32,) = sqlx::query_as(
+                r#"
+                INSERT INTO earning_data_rune_pool_interval (
+                    start_time, end_time, liquidity_fees, block_rewards, earnings,
+                    bonding_earnings, liquidity_earnings, avg_node_count, rune_price_usd
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (start_time, end_time) DO UPDATE SET earnings = EXCLUDED.earnings
+                RETURNING id
+                "#,
+            )
+            .bind(interval.startTime as i64)
+            .bind(interval.endTime as i64)
+            .bind(BigDecimal::from(interval.liquidityFees))
+            .bind(BigDecimal::from(interval.blockRewards))
+            .bind(BigDecimal::from(interval.earnings))
+            .bind(BigDecimal::from(interval.bondingEarnings))
+            .bind(BigDecimal::from(interval.liquidityEarnings))
+            .bind(interval.avgNodeCount)
+            .bind(interval.runePriceUSD)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+            for pool_data in &interval.pools {
+                insert_pool_data(&[(interval_id, pool_data)], &mut *tx)
+                    .await
+                    .unwrap();
+            }
         }
+        tx.commit().await.unwrap();
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore]
+    async fn bulk_insert_benchmark(pool: PgPool) {
+        let intervals = synthetic_intervals();
+        benchmark::compare(
+            "400 earnings intervals x 100 pools",
+            &pool,
+            "earning_data_rune_pool_interval",
+            insert_row_by_row(&intervals, &pool),
+            async {
+                insert_rune_pool_intervals(&intervals, None, &pool)
+                    .await
+                    .unwrap();
+            },
+        )
+        .await;
+
+        let (stored,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM earning_data_pool_data")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, 40_000);
     }
-    Ok(())
 }
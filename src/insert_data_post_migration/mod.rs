@@ -1,4 +1,25 @@
+#[cfg(test)]
+mod benchmark;
 pub mod depth_data_insert_script;
 pub mod earning_data_insert_script;
+pub mod raw_response_insert_script;
 pub mod rune_pool_data_insert_script;
 pub mod swap_data_insert_script;
+
+// How many interval rows an upsert created and how many it overwrote
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Upserted {
+    pub inserted: i64,
+    pub updated: i64,
+}
+
+impl Upserted {
+    // From the `(xmax = 0)` flags an upsert returns, true for fresh rows
+    pub fn from_flags(flags: &[bool]) -> Self {
+        let inserted = flags.iter().filter(|&&fresh| fresh).count() as i64;
+        Upserted {
+            inserted,
+            updated: flags.len() as i64 - inserted,
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::midgard_client::RawResponse;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, Postgres};
+use std::io::Write;
+use std::time::UNIX_EPOCH;
+
+// Archives `raw` verbatim and returns its id, which the interval rows
+// written from it reference as `raw_response_id`
+pub async fn insert_raw_response<'e, E>(raw: &RawResponse, db: E) -> Result<i64, AppError>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let sha256 = format!("{:x}", Sha256::digest(raw.body.as_bytes()));
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(raw.body.as_bytes())?;
+    let body_gzip = gzip.finish()?;
+    let fetched_at = raw
+        .fetched_at
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default();
+    let body_size = i32::try_from(raw.body.len()).map_err(|_| AppError::Overflow {
+        field: "raw response size",
+        value: raw.body.len() as u128,
+    })?;
+
+    let id = sqlx::query_scalar(
+        r#"
+        INSERT INTO ingest_raw_responses (url, fetched_at, http_status, sha256, body_size, body_gzip)
+        VALUES ($1, to_timestamp($2), $3, $4, $5, $6)
+        RETURNING id
+        "#,
+    )
+    .bind(&raw.url)
+    .bind(fetched_at)
+    .bind(raw.status as i16)
+    .bind(sha256)
+    .bind(body_size)
+    .bind(body_gzip)
+    .fetch_one(db)
+    .await?;
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use sqlx::PgPool;
+    use std::io::Read;
+    use std::time::SystemTime;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn stores_the_body_verbatim(db: PgPool) {
+        // the empty string is exactly what the lenient swap parsing hides
+        let raw = RawResponse {
+            url: "https://midgard.ninerealms.com/v2/history/swaps?interval=hour".to_string(),
+            status: 200,
+            fetched_at: SystemTime::now(),
+            body: r#"{"intervals":[{"totalCount":""}]}"#.to_string(),
+        };
+        let id = insert_raw_response(&raw, &db).await.unwrap();
+
+        let (sha256, body_gzip): (String, Vec<u8>) =
+            sqlx::query_as("SELECT sha256, body_gzip FROM ingest_raw_responses WHERE id = $1")
+                .bind(id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        let mut body = String::new();
+        GzDecoder::new(&body_gzip[..])
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(body, raw.body);
+        assert_eq!(sha256, format!("{:x}", Sha256::digest(body.as_bytes())));
+    }
+}
@@ -1,94 +1,164 @@
 use crate::data_structs::rune_pool::RunePoolIntervalsInt;
-use sqlx::PgPool;
+use crate::error::AppError;
+use crate::insert_data_post_migration::Upserted;
+use bigdecimal::BigDecimal;
+use sqlx::{Acquire, Postgres};
 
-pub async fn insert_data(pool: &PgPool, data: RunePoolIntervalsInt) -> Result<(), sqlx::Error> {
-    // Convert meta data with overflow check
-    let meta_start_time: i64 = data.meta.startTime.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartTime {} too large for i64",
-            data.meta.startTime
-        ))
-    })?;
-    let meta_end_time: i64 = data.meta.endTime.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("EndTime {} too large for i64", data.meta.endTime))
-    })?;
-    let meta_start_units: i64 = data.meta.startUnits.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartUnits {} too large for i64",
-            data.meta.startUnits
-        ))
-    })?;
-    let meta_start_count: i64 = data.meta.startCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "StartCount {} too large for i64",
-            data.meta.startCount
-        ))
-    })?;
-    let meta_end_units: i64 = data.meta.endUnits.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("EndUnits {} too large for i64", data.meta.endUnits))
-    })?;
-    let meta_end_count: i64 = data.meta.endCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("EndCount {} too large for i64", data.meta.endCount))
-    })?;
+// `raw_response_id` is the archived response `data` was decoded from
+pub async fn insert_data<'a, A>(
+    db: A,
+    data: RunePoolIntervalsInt,
+    raw_response_id: Option<i64>,
+) -> Result<Upserted, AppError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    // Everything below commits or rolls back as one unit; inside a caller's
+    // transaction this becomes a savepoint
+    let mut tx = db.begin().await?;
 
-    // Insert into RunePoolMeta
+    // Convert meta times with overflow check, amounts are stored as NUMERIC
+    let meta_start_time: i64 = data
+        .meta
+        .startTime
+        .try_into()
+        .map_err(|_| AppError::Overflow {
+            field: "StartTime",
+            value: data.meta.startTime,
+        })?;
+    let meta_end_time: i64 = data
+        .meta
+        .endTime
+        .try_into()
+        .map_err(|_| AppError::Overflow {
+            field: "EndTime",
+            value: data.meta.endTime,
+        })?;
+
+    // Upsert into RunePoolMeta, re-ingesting a window corrects it in place
     sqlx::query(
         r#"
         INSERT INTO Rune_Pool_Data_Meta (startTime, endTime, startUnits, startCount, endUnits, endCount)
         VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (startTime, endTime) DO UPDATE SET
+            startUnits = EXCLUDED.startUnits,
+            startCount = EXCLUDED.startCount,
+            endUnits = EXCLUDED.endUnits,
+            endCount = EXCLUDED.endCount
         "#,
     )
     .bind(meta_start_time)
     .bind(meta_end_time)
-    .bind(meta_start_units)
-    .bind(meta_start_count)
-    .bind(meta_end_units)
-    .bind(meta_end_count)
-    .execute(pool)
+    .bind(BigDecimal::from(data.meta.startUnits))
+    .bind(BigDecimal::from(data.meta.startCount))
+    .bind(BigDecimal::from(data.meta.endUnits))
+    .bind(BigDecimal::from(data.meta.endCount))
+    .execute(&mut *tx)
     .await?;
 
-    // Insert intervals into RunePoolIntervals
+    // Upsert all intervals into RunePoolIntervals in one statement
+    let mut start_times = Vec::with_capacity(data.intervals.len());
+    let mut end_times = Vec::with_capacity(data.intervals.len());
+    let mut counts = Vec::with_capacity(data.intervals.len());
+    let mut units = Vec::with_capacity(data.intervals.len());
     for interval in &data.intervals {
-        let start_time: i64 = interval.startTime.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval StartTime {} too large for i64",
-                interval.startTime
-            ))
-        })?;
-        let end_time: i64 = interval.endTime.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval EndTime {} too large for i64",
-                interval.endTime
-            ))
-        })?;
-        let count: i64 = interval.count.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval Count {} too large for i64",
-                interval.count
-            ))
-        })?;
-        let units: i64 = interval.units.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "Interval Units {} too large for i64",
-                interval.units
-            ))
-        })?;
+        let start_time: i64 = interval
+            .startTime
+            .try_into()
+            .map_err(|_| AppError::Overflow {
+                field: "Interval StartTime",
+                value: interval.startTime,
+            })?;
+        let end_time: i64 = interval
+            .endTime
+            .try_into()
+            .map_err(|_| AppError::Overflow {
+                field: "Interval EndTime",
+                value: interval.endTime,
+            })?;
 
-        sqlx::query(
-            r#"
-            INSERT INTO Rune_Pool_Data_Intervals (startTime, endTime, count, units)
-            VALUES ($1, $2, $3, $4)
-            "#,
-        )
-        .bind(start_time)
-        .bind(end_time)
-        .bind(count)
-        .bind(units)
-        .execute(pool)
-        .await?;
+        start_times.push(start_time);
+        end_times.push(end_time);
+        counts.push(BigDecimal::from(interval.count));
+        units.push(BigDecimal::from(interval.units));
     }
 
+    let fresh: Vec<bool> = sqlx::query_scalar(
+        r#"
+        INSERT INTO Rune_Pool_Data_Intervals (startTime, endTime, count, units, raw_response_id)
+        SELECT t.*, $5 FROM UNNEST($1::bigint[], $2::bigint[], $3::numeric[], $4::numeric[]) AS t
+        ON CONFLICT (startTime, endTime) DO UPDATE SET
+            count = EXCLUDED.count,
+            units = EXCLUDED.units,
+            raw_response_id = EXCLUDED.raw_response_id
+        RETURNING (xmax = 0)
+        "#,
+    )
+    .bind(start_times)
+    .bind(end_times)
+    .bind(counts)
+    .bind(units)
+    .bind(raw_response_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
     println!("Rune pool Data inserted successfully!");
 
-    Ok(())
+    Ok(Upserted::from_flags(&fresh))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::insert_data_post_migration::benchmark;
+    use crate::mock_midgard::series;
+    use sqlx::PgPool;
+
+    // The pre-bulk write path: one round-trip per interval
+    async fn insert_row_by_row(data: &RunePoolIntervalsInt, db: &PgPool) {
+        let mut tx = db.begin().await.unwrap();
+        for interval in &data.intervals {
+            sqlx::query(
+                r#"
+                INSERT INTO Rune_Pool_Data_Intervals (startTime, endTime, count, units)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (startTime, endTime) DO UPDATE SET
+                    count = EXCLUDED.count,
+                    units = EXCLUDED.units
+                "#,
+            )
+            .bind(interval.startTime as i64)
+            .bind(interval.endTime as i64)
+            .bind(BigDecimal::from(interval.count))
+            .bind(BigDecimal::from(interval.units))
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        }
+        tx.commit().await.unwrap();
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore]
+    async fn rune_pool_insert_benchmark(pool: PgPool) {
+        let slow: RunePoolIntervalsInt = benchmark::page(series::runepool);
+        let fast: RunePoolIntervalsInt = benchmark::page(series::runepool);
+        benchmark::compare(
+            "400 RUNEPool intervals",
+            &pool,
+            "Rune_Pool_Data_Intervals",
+            insert_row_by_row(&slow, &pool),
+            async {
+                insert_data(&pool, fast, None).await.unwrap();
+            },
+        )
+        .await;
+
+        let (stored,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM Rune_Pool_Data_Intervals")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, 400);
+    }
 }
@@ -1,174 +1,27 @@
 use crate::data_structs::swap_history::{RunePoolInterval, RunePoolMeta};
-use sqlx;
+use crate::error::AppError;
+use crate::insert_data_post_migration::Upserted;
+use bigdecimal::BigDecimal;
+use sqlx::{Acquire, Executor, Postgres};
 
-pub async fn insert_rune_pool_meta(
+// Pool key of Midgard's all-pools swap series
+pub const AGGREGATE_POOL: &str = "ALL";
+
+pub async fn insert_rune_pool_meta<'e, E>(
     meta: &RunePoolMeta,
-    pool: &sqlx::PgPool,
-) -> Result<(), sqlx::Error> {
-    let start_time: i64 = meta.startTime.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("startTime {} too large for i64", meta.startTime))
-    })?;
-    let end_time: i64 = meta.endTime.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("endTime {} too large for i64", meta.endTime))
-    })?;
-    let to_asset_count: i64 = meta.toAssetCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toAssetCount {} too large for i64",
-            meta.toAssetCount
-        ))
-    })?;
-    let to_rune_count: i64 = meta.toRuneCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toRuneCount {} too large for i64",
-            meta.toRuneCount
-        ))
-    })?;
-    let to_trade_count: i64 = meta.toTradeCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toTradeCount {} too large for i64",
-            meta.toTradeCount
-        ))
-    })?;
-    let from_trade_count: i64 = meta.fromTradeCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "fromTradeCount {} too large for i64",
-            meta.fromTradeCount
-        ))
-    })?;
-    let synth_mint_count: i64 = meta.synthMintCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "synthMintCount {} too large for i64",
-            meta.synthMintCount
-        ))
-    })?;
-    let synth_redeem_count: i64 = meta.synthRedeemCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "synthRedeemCount {} too large for i64",
-            meta.synthRedeemCount
-        ))
-    })?;
-    let total_count: i64 = meta.totalCount.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("totalCount {} too large for i64", meta.totalCount))
-    })?;
-    let to_asset_volume: i64 = meta.toAssetVolume.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toAssetVolume {} too large for i64",
-            meta.toAssetVolume
-        ))
-    })?;
-    let to_rune_volume: i64 = meta.toRuneVolume.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toRuneVolume {} too large for i64",
-            meta.toRuneVolume
-        ))
-    })?;
-    let to_trade_volume: i64 = meta.toTradeVolume.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toTradeVolume {} too large for i64",
-            meta.toTradeVolume
-        ))
-    })?;
-    let from_trade_volume: i64 = meta.fromTradeVolume.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "fromTradeVolume {} too large for i64",
-            meta.fromTradeVolume
-        ))
-    })?;
-    let synth_mint_volume: i64 = meta.synthMintVolume.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "synthMintVolume {} too large for i64",
-            meta.synthMintVolume
-        ))
-    })?;
-    let synth_redeem_volume: i64 = meta.synthRedeemVolume.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "synthRedeemVolume {} too large for i64",
-            meta.synthRedeemVolume
-        ))
-    })?;
-    let total_volume: i64 = meta.totalVolume.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "totalVolume {} too large for i64",
-            meta.totalVolume
-        ))
-    })?;
-    let to_asset_volume_usd: i64 = meta.toAssetVolumeUSD.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toAssetVolumeUSD {} too large for i64",
-            meta.toAssetVolumeUSD
-        ))
+    pool_name: &str,
+    db: E,
+) -> Result<(), AppError>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let start_time: i64 = meta.startTime.try_into().map_err(|_| AppError::Overflow {
+        field: "startTime",
+        value: meta.startTime,
     })?;
-    let to_rune_volume_usd: i64 = meta.toRuneVolumeUSD.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toRuneVolumeUSD {} too large for i64",
-            meta.toRuneVolumeUSD
-        ))
-    })?;
-    let to_trade_volume_usd: i64 = meta.toTradeVolumeUSD.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toTradeVolumeUSD {} too large for i64",
-            meta.toTradeVolumeUSD
-        ))
-    })?;
-    let from_trade_volume_usd: i64 = meta.fromTradeVolumeUSD.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "fromTradeVolumeUSD {} too large for i64",
-            meta.fromTradeVolumeUSD
-        ))
-    })?;
-    let synth_mint_volume_usd: i64 = meta.synthMintVolumeUSD.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "synthMintVolumeUSD {} too large for i64",
-            meta.synthMintVolumeUSD
-        ))
-    })?;
-    let synth_redeem_volume_usd: i64 = meta.synthRedeemVolumeUSD.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "synthRedeemVolumeUSD {} too large for i64",
-            meta.synthRedeemVolumeUSD
-        ))
-    })?;
-    let total_volume_usd: i64 = meta.totalVolumeUSD.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "totalVolumeUSD {} too large for i64",
-            meta.totalVolumeUSD
-        ))
-    })?;
-    let to_asset_fees: i64 = meta.toAssetFees.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toAssetFees {} too large for i64",
-            meta.toAssetFees
-        ))
-    })?;
-    let to_rune_fees: i64 = meta.toRuneFees.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("toRuneFees {} too large for i64", meta.toRuneFees))
-    })?;
-    let to_trade_fees: i64 = meta.toTradeFees.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "toTradeFees {} too large for i64",
-            meta.toTradeFees
-        ))
-    })?;
-    let from_trade_fees: i64 = meta.fromTradeFees.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "fromTradeFees {} too large for i64",
-            meta.fromTradeFees
-        ))
-    })?;
-    let synth_mint_fees: i64 = meta.synthMintFees.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "synthMintFees {} too large for i64",
-            meta.synthMintFees
-        ))
-    })?;
-    let synth_redeem_fees: i64 = meta.synthRedeemFees.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!(
-            "synthRedeemFees {} too large for i64",
-            meta.synthRedeemFees
-        ))
-    })?;
-    let total_fees: i64 = meta.totalFees.try_into().map_err(|_| {
-        sqlx::Error::Protocol(format!("totalFees {} too large for i64", meta.totalFees))
+    let end_time: i64 = meta.endTime.try_into().map_err(|_| AppError::Overflow {
+        field: "endTime",
+        value: meta.endTime,
     })?;
     let to_asset_average_slip: f64 = meta.toAssetAverageSlip;
     let to_rune_average_slip: f64 = meta.toRuneAverageSlip;
@@ -183,7 +36,7 @@ pub async fn insert_rune_pool_meta(
     sqlx::query(
         r#"
         INSERT INTO swap_data_rune_pool_meta (
-            start_time, end_time, to_asset_count, to_rune_count, to_trade_count, from_trade_count,
+            pool, start_time, end_time, to_asset_count, to_rune_count, to_trade_count, from_trade_count,
             synth_mint_count, synth_redeem_count, total_count, to_asset_volume, to_rune_volume,
             to_trade_volume, from_trade_volume, synth_mint_volume, synth_redeem_volume, total_volume,
             to_asset_volume_usd, to_rune_volume_usd, to_trade_volume_usd, from_trade_volume_usd,
@@ -194,40 +47,79 @@ pub async fn insert_rune_pool_meta(
         ) VALUES (
             $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16,
             $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30,
-            $31, $32, $33, $34, $35, $36, $37, $38
-        ) RETURNING id
+            $31, $32, $33, $34, $35, $36, $37, $38, $39
+        )
+        ON CONFLICT (pool, start_time, end_time) DO UPDATE SET
+            to_asset_count = EXCLUDED.to_asset_count,
+            to_rune_count = EXCLUDED.to_rune_count,
+            to_trade_count = EXCLUDED.to_trade_count,
+            from_trade_count = EXCLUDED.from_trade_count,
+            synth_mint_count = EXCLUDED.synth_mint_count,
+            synth_redeem_count = EXCLUDED.synth_redeem_count,
+            total_count = EXCLUDED.total_count,
+            to_asset_volume = EXCLUDED.to_asset_volume,
+            to_rune_volume = EXCLUDED.to_rune_volume,
+            to_trade_volume = EXCLUDED.to_trade_volume,
+            from_trade_volume = EXCLUDED.from_trade_volume,
+            synth_mint_volume = EXCLUDED.synth_mint_volume,
+            synth_redeem_volume = EXCLUDED.synth_redeem_volume,
+            total_volume = EXCLUDED.total_volume,
+            to_asset_volume_usd = EXCLUDED.to_asset_volume_usd,
+            to_rune_volume_usd = EXCLUDED.to_rune_volume_usd,
+            to_trade_volume_usd = EXCLUDED.to_trade_volume_usd,
+            from_trade_volume_usd = EXCLUDED.from_trade_volume_usd,
+            synth_mint_volume_usd = EXCLUDED.synth_mint_volume_usd,
+            synth_redeem_volume_usd = EXCLUDED.synth_redeem_volume_usd,
+            total_volume_usd = EXCLUDED.total_volume_usd,
+            to_asset_fees = EXCLUDED.to_asset_fees,
+            to_rune_fees = EXCLUDED.to_rune_fees,
+            to_trade_fees = EXCLUDED.to_trade_fees,
+            from_trade_fees = EXCLUDED.from_trade_fees,
+            synth_mint_fees = EXCLUDED.synth_mint_fees,
+            synth_redeem_fees = EXCLUDED.synth_redeem_fees,
+            total_fees = EXCLUDED.total_fees,
+            to_asset_average_slip = EXCLUDED.to_asset_average_slip,
+            to_rune_average_slip = EXCLUDED.to_rune_average_slip,
+            to_trade_average_slip = EXCLUDED.to_trade_average_slip,
+            from_trade_average_slip = EXCLUDED.from_trade_average_slip,
+            synth_mint_average_slip = EXCLUDED.synth_mint_average_slip,
+            synth_redeem_average_slip = EXCLUDED.synth_redeem_average_slip,
+            average_slip = EXCLUDED.average_slip,
+            rune_price_usd = EXCLUDED.rune_price_usd
+        RETURNING id
         "#,
     )
+    .bind(pool_name)
     .bind(start_time)
     .bind(end_time)
-    .bind(to_asset_count)
-    .bind(to_rune_count)
-    .bind(to_trade_count)
-    .bind(from_trade_count)
-    .bind(synth_mint_count)
-    .bind(synth_redeem_count)
-    .bind(total_count)
-    .bind(to_asset_volume)
-    .bind(to_rune_volume)
-    .bind(to_trade_volume)
-    .bind(from_trade_volume)
-    .bind(synth_mint_volume)
-    .bind(synth_redeem_volume)
-    .bind(total_volume)
-    .bind(to_asset_volume_usd)
-    .bind(to_rune_volume_usd)
-    .bind(to_trade_volume_usd)
-    .bind(from_trade_volume_usd)
-    .bind(synth_mint_volume_usd)
-    .bind(synth_redeem_volume_usd)
-    .bind(total_volume_usd)
-    .bind(to_asset_fees)
-    .bind(to_rune_fees)
-    .bind(to_trade_fees)
-    .bind(from_trade_fees)
-    .bind(synth_mint_fees)
-    .bind(synth_redeem_fees)
-    .bind(total_fees)
+    .bind(BigDecimal::from(meta.toAssetCount))
+    .bind(BigDecimal::from(meta.toRuneCount))
+    .bind(BigDecimal::from(meta.toTradeCount))
+    .bind(BigDecimal::from(meta.fromTradeCount))
+    .bind(BigDecimal::from(meta.synthMintCount))
+    .bind(BigDecimal::from(meta.synthRedeemCount))
+    .bind(BigDecimal::from(meta.totalCount))
+    .bind(BigDecimal::from(meta.toAssetVolume))
+    .bind(BigDecimal::from(meta.toRuneVolume))
+    .bind(BigDecimal::from(meta.toTradeVolume))
+    .bind(BigDecimal::from(meta.fromTradeVolume))
+    .bind(BigDecimal::from(meta.synthMintVolume))
+    .bind(BigDecimal::from(meta.synthRedeemVolume))
+    .bind(BigDecimal::from(meta.totalVolume))
+    .bind(BigDecimal::from(meta.toAssetVolumeUSD))
+    .bind(BigDecimal::from(meta.toRuneVolumeUSD))
+    .bind(BigDecimal::from(meta.toTradeVolumeUSD))
+    .bind(BigDecimal::from(meta.fromTradeVolumeUSD))
+    .bind(BigDecimal::from(meta.synthMintVolumeUSD))
+    .bind(BigDecimal::from(meta.synthRedeemVolumeUSD))
+    .bind(BigDecimal::from(meta.totalVolumeUSD))
+    .bind(BigDecimal::from(meta.toAssetFees))
+    .bind(BigDecimal::from(meta.toRuneFees))
+    .bind(BigDecimal::from(meta.toTradeFees))
+    .bind(BigDecimal::from(meta.fromTradeFees))
+    .bind(BigDecimal::from(meta.synthMintFees))
+    .bind(BigDecimal::from(meta.synthRedeemFees))
+    .bind(BigDecimal::from(meta.totalFees))
     .bind(to_asset_average_slip)
     .bind(to_rune_average_slip)
     .bind(to_trade_average_slip)
@@ -236,7 +128,7 @@ pub async fn insert_rune_pool_meta(
     .bind(synth_redeem_average_slip)
     .bind(average_slip)
     .bind(rune_price_usd)
-    .fetch_one(pool)
+    .fetch_one(db)
     .await?;
 
     std::println!("The data has been inserted into swap_data_rune_pool_meta");
@@ -244,145 +136,271 @@ pub async fn insert_rune_pool_meta(
     Ok(())
 }
 
-pub async fn insert_rune_pool_intervals(
+pub async fn insert_rune_pool_intervals<'a, A>(
     intervals: &[RunePoolInterval], // Accepts a slice of intervals
-    pool: &sqlx::PgPool,
-) -> Result<(), sqlx::Error> {
+    pool_name: &str,                // Midgard pool, or AGGREGATE_POOL
+    raw_response_id: Option<i64>,   // archived response the intervals came from
+    db: A,
+) -> Result<Upserted, AppError>
+where
+    A: Acquire<'a, Database = Postgres>,
+{
+    let mut tx = db.begin().await?;
+
+    // Column-wise buffers for a single UNNEST upsert of the whole slice
+    let mut start_times = Vec::with_capacity(intervals.len());
+    let mut end_times = Vec::with_capacity(intervals.len());
+    let mut to_asset_counts = Vec::with_capacity(intervals.len());
+    let mut to_rune_counts = Vec::with_capacity(intervals.len());
+    let mut to_trade_counts = Vec::with_capacity(intervals.len());
+    let mut from_trade_counts = Vec::with_capacity(intervals.len());
+    let mut synth_mint_counts = Vec::with_capacity(intervals.len());
+    let mut synth_redeem_counts = Vec::with_capacity(intervals.len());
+    let mut total_counts = Vec::with_capacity(intervals.len());
+    let mut to_asset_volumes = Vec::with_capacity(intervals.len());
+    let mut to_rune_volumes = Vec::with_capacity(intervals.len());
+    let mut to_trade_volumes = Vec::with_capacity(intervals.len());
+    let mut from_trade_volumes = Vec::with_capacity(intervals.len());
+    let mut synth_mint_volumes = Vec::with_capacity(intervals.len());
+    let mut synth_redeem_volumes = Vec::with_capacity(intervals.len());
+    let mut total_volumes = Vec::with_capacity(intervals.len());
+    let mut to_asset_volumes_usd = Vec::with_capacity(intervals.len());
+    let mut to_rune_volumes_usd = Vec::with_capacity(intervals.len());
+    let mut to_trade_volumes_usd = Vec::with_capacity(intervals.len());
+    let mut from_trade_volumes_usd = Vec::with_capacity(intervals.len());
+    let mut synth_mint_volumes_usd = Vec::with_capacity(intervals.len());
+    let mut synth_redeem_volumes_usd = Vec::with_capacity(intervals.len());
+    let mut total_volumes_usd = Vec::with_capacity(intervals.len());
+    let mut to_asset_fees = Vec::with_capacity(intervals.len());
+    let mut to_rune_fees = Vec::with_capacity(intervals.len());
+    let mut to_trade_fees = Vec::with_capacity(intervals.len());
+    let mut from_trade_fees = Vec::with_capacity(intervals.len());
+    let mut synth_mint_fees = Vec::with_capacity(intervals.len());
+    let mut synth_redeem_fees = Vec::with_capacity(intervals.len());
+    let mut total_fees = Vec::with_capacity(intervals.len());
+    let mut to_asset_average_slips = Vec::with_capacity(intervals.len());
+    let mut to_rune_average_slips = Vec::with_capacity(intervals.len());
+    let mut to_trade_average_slips = Vec::with_capacity(intervals.len());
+    let mut from_trade_average_slips = Vec::with_capacity(intervals.len());
+    let mut synth_mint_average_slips = Vec::with_capacity(intervals.len());
+    let mut synth_redeem_average_slips = Vec::with_capacity(intervals.len());
+    let mut average_slips = Vec::with_capacity(intervals.len());
+    let mut rune_prices_usd = Vec::with_capacity(intervals.len());
     for interval in intervals {
-        let start_time: i64 = interval.startTime.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "startTime {} too large for i64",
-                interval.startTime
-            ))
-        })?;
-        let end_time: i64 = interval.endTime.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!("endTime {} too large for i64", interval.endTime))
-        })?;
-        let to_asset_count: i64 = interval.toAssetCount.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "toAssetCount {} too large for i64",
-                interval.toAssetCount
-            ))
-        })?;
-        let to_rune_count: i64 = interval.toRuneCount.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "toRuneCount {} too large for i64",
-                interval.toRuneCount
-            ))
-        })?;
-        let to_trade_count: i64 = interval.toTradeCount.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "toTradeCount {} too large for i64",
-                interval.toTradeCount
-            ))
-        })?;
-        let from_trade_count: i64 = interval.fromTradeCount.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "fromTradeCount {} too large for i64",
-                interval.fromTradeCount
-            ))
-        })?;
-        let synth_mint_count: i64 = interval.synthMintCount.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "synthMintCount {} too large for i64",
-                interval.synthMintCount
-            ))
-        })?;
-        let synth_redeem_count: i64 = interval.synthRedeemCount.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "synthRedeemCount {} too large for i64",
-                interval.synthRedeemCount
-            ))
-        })?;
-        let total_count: i64 = interval.totalCount.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "totalCount {} too large for i64",
-                interval.totalCount
-            ))
-        })?;
-        let to_asset_volume: i64 = interval.toAssetVolume.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "toAssetVolume {} too large for i64",
-                interval.toAssetVolume
-            ))
-        })?;
-        let to_rune_volume: i64 = interval.toRuneVolume.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "toRuneVolume {} too large for i64",
-                interval.toRuneVolume
-            ))
-        })?;
-        let to_trade_volume: i64 = interval.toTradeVolume.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "toTradeVolume {} too large for i64",
-                interval.toTradeVolume
-            ))
-        })?;
-        let from_trade_volume: i64 = interval.fromTradeVolume.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "fromTradeVolume {} too large for i64",
-                interval.fromTradeVolume
-            ))
-        })?;
-        let synth_mint_volume: i64 = interval.synthMintVolume.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "synthMintVolume {} too large for i64",
-                interval.synthMintVolume
-            ))
-        })?;
-        let synth_redeem_volume: i64 = interval.synthRedeemVolume.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "synthRedeemVolume {} too large for i64",
-                interval.synthRedeemVolume
-            ))
-        })?;
-        let total_volume: i64 = interval.totalVolume.try_into().map_err(|_| {
-            sqlx::Error::Protocol(format!(
-                "totalVolume {} too large for i64",
-                interval.totalVolume
-            ))
-        })?;
-        let rune_price_usd: f64 = interval.runePriceUSD;
+        let start_time: i64 = interval
+            .startTime
+            .try_into()
+            .map_err(|_| AppError::Overflow {
+                field: "startTime",
+                value: interval.startTime,
+            })?;
+        let end_time: i64 = interval
+            .endTime
+            .try_into()
+            .map_err(|_| AppError::Overflow {
+                field: "endTime",
+                value: interval.endTime,
+            })?;
+
+        start_times.push(start_time);
+        end_times.push(end_time);
+        to_asset_counts.push(BigDecimal::from(interval.toAssetCount));
+        to_rune_counts.push(BigDecimal::from(interval.toRuneCount));
+        to_trade_counts.push(BigDecimal::from(interval.toTradeCount));
+        from_trade_counts.push(BigDecimal::from(interval.fromTradeCount));
+        synth_mint_counts.push(BigDecimal::from(interval.synthMintCount));
+        synth_redeem_counts.push(BigDecimal::from(interval.synthRedeemCount));
+        total_counts.push(BigDecimal::from(interval.totalCount));
+        to_asset_volumes.push(BigDecimal::from(interval.toAssetVolume));
+        to_rune_volumes.push(BigDecimal::from(interval.toRuneVolume));
+        to_trade_volumes.push(BigDecimal::from(interval.toTradeVolume));
+        from_trade_volumes.push(BigDecimal::from(interval.fromTradeVolume));
+        synth_mint_volumes.push(BigDecimal::from(interval.synthMintVolume));
+        synth_redeem_volumes.push(BigDecimal::from(interval.synthRedeemVolume));
+        total_volumes.push(BigDecimal::from(interval.totalVolume));
+        to_asset_volumes_usd.push(BigDecimal::from(interval.toAssetVolumeUSD));
+        to_rune_volumes_usd.push(BigDecimal::from(interval.toRuneVolumeUSD));
+        to_trade_volumes_usd.push(BigDecimal::from(interval.toTradeVolumeUSD));
+        from_trade_volumes_usd.push(BigDecimal::from(interval.fromTradeVolumeUSD));
+        synth_mint_volumes_usd.push(BigDecimal::from(interval.synthMintVolumeUSD));
+        synth_redeem_volumes_usd.push(BigDecimal::from(interval.synthRedeemVolumeUSD));
+        total_volumes_usd.push(BigDecimal::from(interval.totalVolumeUSD));
+        to_asset_fees.push(BigDecimal::from(interval.toAssetFees));
+        to_rune_fees.push(BigDecimal::from(interval.toRuneFees));
+        to_trade_fees.push(BigDecimal::from(interval.toTradeFees));
+        from_trade_fees.push(BigDecimal::from(interval.fromTradeFees));
+        synth_mint_fees.push(BigDecimal::from(interval.synthMintFees));
+        synth_redeem_fees.push(BigDecimal::from(interval.synthRedeemFees));
+        total_fees.push(BigDecimal::from(interval.totalFees));
+        to_asset_average_slips.push(interval.toAssetAverageSlip);
+        to_rune_average_slips.push(interval.toRuneAverageSlip);
+        to_trade_average_slips.push(interval.toTradeAverageSlip);
+        from_trade_average_slips.push(interval.fromTradeAverageSlip);
+        synth_mint_average_slips.push(interval.synthMintAverageSlip);
+        synth_redeem_average_slips.push(interval.synthRedeemAverageSlip);
+        average_slips.push(interval.averageSlip);
+        rune_prices_usd.push(interval.runePriceUSD);
+    }
 
-        // Insert each interval data into the database
-        sqlx::query(
-            r#"
+    // Upsert every interval so a re-fetched window corrects revised values
+    let fresh: Vec<bool> = sqlx::query_scalar(
+        r#"
         INSERT INTO swap_data_rune_pool_interval (
-            start_time, end_time, to_asset_count, to_rune_count, to_trade_count, from_trade_count,
+            pool, start_time, end_time, to_asset_count, to_rune_count, to_trade_count, from_trade_count,
             synth_mint_count, synth_redeem_count, total_count, to_asset_volume, to_rune_volume,
             to_trade_volume, from_trade_volume, synth_mint_volume, synth_redeem_volume, total_volume,
-            to_asset_average_slip, to_rune_average_slip, average_slip, rune_price_usd
-        ) VALUES (
-            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16,
-            $17, $18, $19, $20
+            to_asset_volume_usd, to_rune_volume_usd, to_trade_volume_usd, from_trade_volume_usd,
+            synth_mint_volume_usd, synth_redeem_volume_usd, total_volume_usd, to_asset_fees, to_rune_fees,
+            to_trade_fees, from_trade_fees, synth_mint_fees, synth_redeem_fees, total_fees,
+            to_asset_average_slip, to_rune_average_slip, to_trade_average_slip, from_trade_average_slip,
+            synth_mint_average_slip, synth_redeem_average_slip, average_slip, rune_price_usd,
+            raw_response_id
         )
+        SELECT $1, t.*, $40 FROM UNNEST(
+            $2::bigint[], $3::bigint[], $4::numeric[], $5::numeric[], $6::numeric[], $7::numeric[],
+            $8::numeric[], $9::numeric[], $10::numeric[], $11::numeric[], $12::numeric[],
+            $13::numeric[], $14::numeric[], $15::numeric[], $16::numeric[], $17::numeric[],
+            $18::numeric[], $19::numeric[], $20::numeric[], $21::numeric[], $22::numeric[],
+            $23::numeric[], $24::numeric[], $25::numeric[], $26::numeric[], $27::numeric[],
+            $28::numeric[], $29::numeric[], $30::numeric[], $31::numeric[],
+            $32::float8[], $33::float8[], $34::float8[], $35::float8[], $36::float8[],
+            $37::float8[], $38::float8[], $39::float8[]
+        ) AS t
+        ON CONFLICT (pool, start_time, end_time) DO UPDATE SET
+            to_asset_count = EXCLUDED.to_asset_count,
+            to_rune_count = EXCLUDED.to_rune_count,
+            to_trade_count = EXCLUDED.to_trade_count,
+            from_trade_count = EXCLUDED.from_trade_count,
+            synth_mint_count = EXCLUDED.synth_mint_count,
+            synth_redeem_count = EXCLUDED.synth_redeem_count,
+            total_count = EXCLUDED.total_count,
+            to_asset_volume = EXCLUDED.to_asset_volume,
+            to_rune_volume = EXCLUDED.to_rune_volume,
+            to_trade_volume = EXCLUDED.to_trade_volume,
+            from_trade_volume = EXCLUDED.from_trade_volume,
+            synth_mint_volume = EXCLUDED.synth_mint_volume,
+            synth_redeem_volume = EXCLUDED.synth_redeem_volume,
+            total_volume = EXCLUDED.total_volume,
+            to_asset_volume_usd = EXCLUDED.to_asset_volume_usd,
+            to_rune_volume_usd = EXCLUDED.to_rune_volume_usd,
+            to_trade_volume_usd = EXCLUDED.to_trade_volume_usd,
+            from_trade_volume_usd = EXCLUDED.from_trade_volume_usd,
+            synth_mint_volume_usd = EXCLUDED.synth_mint_volume_usd,
+            synth_redeem_volume_usd = EXCLUDED.synth_redeem_volume_usd,
+            total_volume_usd = EXCLUDED.total_volume_usd,
+            to_asset_fees = EXCLUDED.to_asset_fees,
+            to_rune_fees = EXCLUDED.to_rune_fees,
+            to_trade_fees = EXCLUDED.to_trade_fees,
+            from_trade_fees = EXCLUDED.from_trade_fees,
+            synth_mint_fees = EXCLUDED.synth_mint_fees,
+            synth_redeem_fees = EXCLUDED.synth_redeem_fees,
+            total_fees = EXCLUDED.total_fees,
+            to_asset_average_slip = EXCLUDED.to_asset_average_slip,
+            to_rune_average_slip = EXCLUDED.to_rune_average_slip,
+            to_trade_average_slip = EXCLUDED.to_trade_average_slip,
+            from_trade_average_slip = EXCLUDED.from_trade_average_slip,
+            synth_mint_average_slip = EXCLUDED.synth_mint_average_slip,
+            synth_redeem_average_slip = EXCLUDED.synth_redeem_average_slip,
+            average_slip = EXCLUDED.average_slip,
+            rune_price_usd = EXCLUDED.rune_price_usd,
+            raw_response_id = EXCLUDED.raw_response_id
+        RETURNING (xmax = 0)
         "#,
-        )
-        .bind(start_time)
-        .bind(end_time)
-        .bind(to_asset_count)
-        .bind(to_rune_count)
-        .bind(to_trade_count)
-        .bind(from_trade_count)
-        .bind(synth_mint_count)
-        .bind(synth_redeem_count)
-        .bind(total_count)
-        .bind(to_asset_volume)
-        .bind(to_rune_volume)
-        .bind(to_trade_volume)
-        .bind(from_trade_volume)
-        .bind(synth_mint_volume)
-        .bind(synth_redeem_volume)
-        .bind(total_volume)
-        .bind(interval.toAssetAverageSlip)
-        .bind(interval.toRuneAverageSlip)
-        .bind(interval.averageSlip)
-        .bind(rune_price_usd)
-        .execute(pool)
-        .await?;
+    )
+    .bind(pool_name)
+    .bind(start_times)
+    .bind(end_times)
+    .bind(to_asset_counts)
+    .bind(to_rune_counts)
+    .bind(to_trade_counts)
+    .bind(from_trade_counts)
+    .bind(synth_mint_counts)
+    .bind(synth_redeem_counts)
+    .bind(total_counts)
+    .bind(to_asset_volumes)
+    .bind(to_rune_volumes)
+    .bind(to_trade_volumes)
+    .bind(from_trade_volumes)
+    .bind(synth_mint_volumes)
+    .bind(synth_redeem_volumes)
+    .bind(total_volumes)
+    .bind(to_asset_volumes_usd)
+    .bind(to_rune_volumes_usd)
+    .bind(to_trade_volumes_usd)
+    .bind(from_trade_volumes_usd)
+    .bind(synth_mint_volumes_usd)
+    .bind(synth_redeem_volumes_usd)
+    .bind(total_volumes_usd)
+    .bind(to_asset_fees)
+    .bind(to_rune_fees)
+    .bind(to_trade_fees)
+    .bind(from_trade_fees)
+    .bind(synth_mint_fees)
+    .bind(synth_redeem_fees)
+    .bind(total_fees)
+    .bind(to_asset_average_slips)
+    .bind(to_rune_average_slips)
+    .bind(to_trade_average_slips)
+    .bind(from_trade_average_slips)
+    .bind(synth_mint_average_slips)
+    .bind(synth_redeem_average_slips)
+    .bind(average_slips)
+    .bind(rune_prices_usd)
+    .bind(raw_response_id)
+    .fetch_all(&mut *tx)
+    .await?;
 
-        std::println!("Interval data inserted successfully.");
+    std::println!(
+        "{} swap intervals inserted for {}",
+        intervals.len(),
+        pool_name
+    );
+
+    tx.commit().await?;
+    Ok(Upserted::from_flags(&fresh))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structs::swap_history::RootSwapDetails;
+    use crate::insert_data_post_migration::benchmark;
+    use crate::mock_midgard::series;
+    use sqlx::PgPool;
+
+    // The pre-bulk write path: one round-trip per interval
+    async fn insert_row_by_row(intervals: &[RunePoolInterval], db: &PgPool) {
+        let mut tx = db.begin().await.unwrap();
+        for interval in intervals {
+            insert_rune_pool_intervals(std::slice::from_ref(interval), "BTC.BTC", None, &mut *tx)
+                .await
+                .unwrap();
+        }
+        tx.commit().await.unwrap();
     }
 
-    Ok(())
+    #[sqlx::test(migrations = "./migrations")]
+    #[ignore]
+    async fn swap_insert_benchmark(pool: PgPool) {
+        let swaps = |window: &series::Window| series::swaps(Some("BTC.BTC"), window);
+        let data: RootSwapDetails = benchmark::page(swaps);
+        benchmark::compare(
+            "400 swap intervals",
+            &pool,
+            "swap_data_rune_pool_interval",
+            insert_row_by_row(&data.intervals, &pool),
+            async {
+                insert_rune_pool_intervals(&data.intervals, "BTC.BTC", None, &pool)
+                    .await
+                    .unwrap();
+            },
+        )
+        .await;
+
+        let (stored,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM swap_data_rune_pool_interval")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, 400);
+    }
 }
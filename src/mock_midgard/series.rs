@@ -0,0 +1,464 @@
+use crate::fetch_data_from_midgard::midgard_client::Interval;
+use serde_json::{json, Map, Value};
+
+// The synthetic history starts here (2022-03-22, around when mainnet went
+// live); windows before it come back empty, so backfills terminate
+pub const GENESIS: i64 = 1_647_907_200;
+
+// Midgard's own cap on `count` and on buckets per response
+pub const MAX_BUCKETS: i64 = 400;
+
+const DEFAULT_COUNT: i64 = 10;
+
+// (asset, status) as listed by `/v2/pools`
+pub const POOLS: [(&str, &str); 3] = [
+    ("BTC.BTC", "available"),
+    ("ETH.ETH", "available"),
+    ("DOGE.DOGE", "staged"),
+];
+
+// Calendar intervals use a fixed average length; good enough for a mock
+fn width(interval: Interval) -> i64 {
+    interval.fixed_seconds().unwrap_or(match interval {
+        Interval::Week => 7 * 86_400,
+        Interval::Month => 30 * 86_400,
+        Interval::Quarter => 91 * 86_400,
+        _ => 365 * 86_400,
+    })
+}
+
+// The buckets one response covers, plus the window they were cut from so
+// empty responses still get a meta time range
+#[derive(Debug)]
+pub struct Window {
+    pub start: i64,
+    pub end: i64,
+    pub buckets: Vec<(i64, i64)>,
+}
+
+// Resolves Midgard's `count`/`from`/`to` combinations: `from` and `to`
+// cover everything between them, one of them plus `count` walks from that
+// end, and neither ends with the bucket `now` falls in. `to` is exclusive.
+pub fn window(
+    interval: Interval,
+    count: Option<u32>,
+    from: Option<i64>,
+    to: Option<i64>,
+    now: i64,
+) -> Result<Window, String> {
+    let width = width(interval);
+    let count = count.map(i64::from);
+    if count.is_some() && from.is_some() && to.is_some() {
+        return Err("count, from and to cannot all be set".to_string());
+    }
+    if count.is_some_and(|count| !(1..=MAX_BUCKETS).contains(&count)) {
+        return Err(format!("count must be between 1 and {}", MAX_BUCKETS));
+    }
+
+    let floor = |t: i64| t.div_euclid(width) * width;
+    let ceil = |t: i64| floor(t + width - 1);
+    let (start, end) = match (from, to) {
+        (Some(from), Some(to)) if to < from => return Err("to must not be before from".into()),
+        (Some(from), Some(to)) => (floor(from), ceil(to)),
+        (Some(from), None) => (
+            floor(from),
+            floor(from) + width * count.unwrap_or(DEFAULT_COUNT),
+        ),
+        (None, to) => {
+            let end = to.map(ceil).unwrap_or(floor(now) + width);
+            (end - width * count.unwrap_or(DEFAULT_COUNT), end)
+        }
+    };
+    if (end - start) / width > MAX_BUCKETS {
+        return Err(format!(
+            "the window spans more than {} intervals",
+            MAX_BUCKETS
+        ));
+    }
+
+    let buckets = (start..end)
+        .step_by(width as usize)
+        .filter(|&bucket| bucket >= GENESIS && bucket <= now)
+        .map(|bucket| (bucket, bucket + width))
+        .collect();
+    Ok(Window {
+        start,
+        end,
+        buckets,
+    })
+}
+
+// FNV-1a over the pool, field and bucket, so every value is reproducible
+fn noise(pool: &str, field: &str, start: i64) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in pool.bytes().chain(field.bytes()).chain(start.to_le_bytes()) {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// Between `base` and twice `base`
+fn amount(pool: &str, field: &str, start: i64, base: u64) -> u64 {
+    base + noise(pool, field, start) % base
+}
+
+// Within 10% of `base`
+fn ratio(pool: &str, field: &str, start: i64, base: f64) -> f64 {
+    base * (1.0 + (noise(pool, field, start) % 1_000) as f64 / 10_000.0)
+}
+
+fn decimal(value: f64) -> Value {
+    Value::String(format!("{:.10}", value))
+}
+
+fn integer(value: u64) -> Value {
+    Value::String(value.to_string())
+}
+
+fn times(start: i64, end: i64) -> Map<String, Value> {
+    let mut row = Map::new();
+    row.insert("startTime".into(), integer(start as u64));
+    row.insert("endTime".into(), integer(end as u64));
+    row
+}
+
+fn number(row: &Map<String, Value>, field: &str) -> u64 {
+    row[field]
+        .as_str()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+fn float(row: &Map<String, Value>, field: &str) -> f64 {
+    row[field]
+        .as_str()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.0)
+}
+
+// Midgard's meta is the window's aggregate: totals add up, averages average
+// and the rest is taken from the last interval
+fn aggregate(
+    window: &Window,
+    intervals: &[Map<String, Value>],
+    sums: &[&str],
+    means: &[&str],
+    lasts: &[&str],
+) -> Map<String, Value> {
+    let mut meta = times(window.start, window.end);
+    let first = intervals.first().and_then(|row| row.get("startTime"));
+    let last = intervals.last().and_then(|row| row.get("endTime"));
+    if let (Some(first), Some(last)) = (first, last) {
+        meta.insert("startTime".into(), first.clone());
+        meta.insert("endTime".into(), last.clone());
+    }
+    for field in sums {
+        let total = intervals.iter().map(|row| number(row, field)).sum();
+        meta.insert(field.to_string(), integer(total));
+    }
+    for field in means {
+        let total: f64 = intervals.iter().map(|row| float(row, field)).sum();
+        meta.insert(
+            field.to_string(),
+            decimal(total / intervals.len().max(1) as f64),
+        );
+    }
+    for field in lasts {
+        let value = intervals
+            .last()
+            .map(|row| row[*field].clone())
+            .unwrap_or_else(|| integer(0));
+        meta.insert(field.to_string(), value);
+    }
+    meta
+}
+
+fn response(meta: Map<String, Value>, intervals: Vec<Map<String, Value>>) -> Value {
+    json!({ "meta": meta, "intervals": intervals })
+}
+
+pub fn runepool(window: &Window) -> Value {
+    let intervals: Vec<_> = window
+        .buckets
+        .iter()
+        .map(|&(start, end)| {
+            let mut row = times(start, end);
+            // slowly growing, like the real RUNEPool
+            let age = ((start - GENESIS) / 3_600) as u64;
+            row.insert("count".into(), integer(1_000 + age / 24));
+            row.insert("units".into(), integer(10_u64.pow(14) + age * 1_000_003));
+            row
+        })
+        .collect();
+
+    let mut meta = aggregate(window, &intervals, &[], &[], &[]);
+    for (prefix, row) in [("start", intervals.first()), ("end", intervals.last())] {
+        for field in ["Units", "Count"] {
+            let value = row
+                .map(|row| row[&field.to_lowercase()].clone())
+                .unwrap_or_else(|| integer(0));
+            meta.insert(format!("{}{}", prefix, field), value);
+        }
+    }
+    response(meta, intervals)
+}
+
+// One bucket's snapshot of the pool at its end
+fn depth(pool: &str, start: i64, end: i64) -> Map<String, Value> {
+    let mut row = times(start, end);
+    let asset_depth = amount(pool, "assetDepth", start, 100_000_000_000);
+    let rune_depth = amount(pool, "runeDepth", start, 900_000_000_000_000);
+    let asset_price = rune_depth as f64 / asset_depth as f64;
+    let units = amount(pool, "units", start, 60_000_000_000_000);
+    let synth_units = amount(pool, "synthUnits", start, 400_000_000_000);
+    row.insert("assetDepth".into(), integer(asset_depth));
+    row.insert("runeDepth".into(), integer(rune_depth));
+    row.insert("assetPrice".into(), decimal(asset_price));
+    row.insert(
+        "assetPriceUSD".into(),
+        decimal(asset_price * ratio(pool, "runePriceUSD", start, 5.0)),
+    );
+    row.insert("liquidityUnits".into(), integer(units - synth_units));
+    row.insert(
+        "membersCount".into(),
+        integer(amount(pool, "membersCount", start, 10_000)),
+    );
+    row.insert("synthUnits".into(), integer(synth_units));
+    row.insert(
+        "synthSupply".into(),
+        integer(amount(pool, "synthSupply", start, 1_000_000_000)),
+    );
+    row.insert("units".into(), integer(units));
+    row.insert("luvi".into(), decimal(ratio(pool, "luvi", start, 0.03)));
+    row
+}
+
+pub fn depths(pool: &str, window: &Window) -> Value {
+    let intervals: Vec<_> = window
+        .buckets
+        .iter()
+        .map(|&(start, end)| depth(pool, start, end))
+        .collect();
+
+    let mut meta = aggregate(window, &intervals, &[], &[], &[]);
+    for (prefix, row) in [("start", intervals.first()), ("end", intervals.last())] {
+        for (field, source) in [
+            ("AssetDepth", "assetDepth"),
+            ("RuneDepth", "runeDepth"),
+            ("LPUnits", "liquidityUnits"),
+            ("MemberCount", "membersCount"),
+            ("SynthUnits", "synthUnits"),
+        ] {
+            let value = row
+                .map(|row| row[source].clone())
+                .unwrap_or_else(|| integer(0));
+            meta.insert(format!("{}{}", prefix, field), value);
+        }
+    }
+    // the window opens with the snapshot of the bucket before it, as far as
+    // the history goes back
+    let opening = window.buckets.first().map(|&(start, end)| {
+        let before = start - (end - start);
+        match before >= GENESIS {
+            true => depth(pool, before, start),
+            false => intervals[0].clone(),
+        }
+    });
+    let (loss, increase) = match (opening, intervals.last()) {
+        (Some(opening), Some(last)) => {
+            let r = float(last, "assetPrice") / float(&opening, "assetPrice");
+            (
+                decimal(2.0 * r.sqrt() / (1.0 + r)),
+                decimal(float(last, "luvi") / float(&opening, "luvi")),
+            )
+        }
+        _ => (Value::String("NaN".into()), Value::String("NaN".into())),
+    };
+    meta.insert("priceShiftLoss".into(), loss);
+    meta.insert("luviIncrease".into(), increase);
+    response(meta, intervals)
+}
+
+const POOL_EARNING_SUMS: [&str; 6] = [
+    "assetLiquidityFees",
+    "runeLiquidityFees",
+    "totalLiquidityFeesRune",
+    "saverEarning",
+    "rewards",
+    "earnings",
+];
+
+fn pool_earnings(pool: &str, start: i64) -> Map<String, Value> {
+    let asset_fees = amount(pool, "assetLiquidityFees", start, 300_000_000);
+    let rune_fees = amount(pool, "runeLiquidityFees", start, 150_000_000);
+    let rewards = amount(pool, "rewards", start, 200_000_000);
+    let mut row = Map::new();
+    row.insert("pool".into(), Value::String(pool.to_string()));
+    row.insert("assetLiquidityFees".into(), integer(asset_fees));
+    row.insert("runeLiquidityFees".into(), integer(rune_fees));
+    row.insert(
+        "totalLiquidityFeesRune".into(),
+        integer(asset_fees + rune_fees),
+    );
+    row.insert(
+        "saverEarning".into(),
+        integer(amount(pool, "saverEarning", start, 1_000_000)),
+    );
+    row.insert("rewards".into(), integer(rewards));
+    row.insert("earnings".into(), integer(asset_fees + rune_fees + rewards));
+    row
+}
+
+fn available_pools() -> impl Iterator<Item = &'static str> {
+    POOLS
+        .iter()
+        .filter(|(_, status)| *status == "available")
+        .map(|(asset, _)| *asset)
+}
+
+pub fn earnings(window: &Window) -> Value {
+    let intervals: Vec<_> = window
+        .buckets
+        .iter()
+        .map(|&(start, end)| {
+            let pools: Vec<_> = available_pools()
+                .map(|pool| pool_earnings(pool, start))
+                .collect();
+            let fees: u64 = pools
+                .iter()
+                .map(|p| number(p, "totalLiquidityFeesRune"))
+                .sum();
+            let block_rewards: u64 = pools.iter().map(|p| number(p, "rewards")).sum();
+            let earnings = fees + block_rewards;
+            let bonding = earnings / 10 * 7;
+
+            let mut row = times(start, end);
+            row.insert("liquidityFees".into(), integer(fees));
+            row.insert("blockRewards".into(), integer(block_rewards));
+            row.insert("earnings".into(), integer(earnings));
+            row.insert("bondingEarnings".into(), integer(bonding));
+            row.insert("liquidityEarnings".into(), integer(earnings - bonding));
+            row.insert(
+                "avgNodeCount".into(),
+                decimal(ratio("", "avgNodeCount", start, 100.0)),
+            );
+            row.insert(
+                "runePriceUSD".into(),
+                decimal(ratio("", "runePriceUSD", start, 5.0)),
+            );
+            row.insert("pools".into(), Value::from(pools));
+            row
+        })
+        .collect();
+
+    let mut meta = aggregate(
+        window,
+        &intervals,
+        &[
+            "liquidityFees",
+            "blockRewards",
+            "earnings",
+            "bondingEarnings",
+            "liquidityEarnings",
+        ],
+        &["avgNodeCount"],
+        &["runePriceUSD"],
+    );
+    let pools: Vec<_> = available_pools()
+        .map(|pool| {
+            let rows: Vec<_> = intervals
+                .iter()
+                .flat_map(|row| row["pools"].as_array().into_iter().flatten())
+                .filter_map(Value::as_object)
+                .filter(|row| row["pool"] == pool)
+                .cloned()
+                .collect();
+            let mut totals = aggregate(window, &rows, &POOL_EARNING_SUMS, &[], &[]);
+            totals.remove("startTime");
+            totals.remove("endTime");
+            totals.insert("pool".into(), Value::String(pool.to_string()));
+            totals
+        })
+        .collect();
+    meta.insert("pools".into(), Value::from(pools));
+    response(meta, intervals)
+}
+
+// Swap directions; the totals add them up
+const SWAP_KINDS: [&str; 6] = [
+    "toAsset",
+    "toRune",
+    "toTrade",
+    "fromTrade",
+    "synthMint",
+    "synthRedeem",
+];
+
+// `pool` narrows to one pool, None is the all-pools aggregate
+pub fn swaps(pool: Option<&str>, window: &Window) -> Value {
+    let seed = pool.unwrap_or("");
+    let intervals: Vec<_> = window
+        .buckets
+        .iter()
+        .map(|&(start, end)| {
+            let mut row = times(start, end);
+            let mut totals = [0_u64; 4];
+            let mut slips = 0.0;
+            for kind in SWAP_KINDS {
+                let count = amount(seed, kind, start, 50);
+                let volume = count * amount(seed, "volume", start, 20_000_000);
+                let volume_usd = volume / 100 * amount(seed, "usd", start, 500);
+                let fees = volume / 1_000;
+                let slip = ratio(seed, kind, start, 4.0);
+                for (i, (field, value)) in [
+                    ("Count", count),
+                    ("Volume", volume),
+                    ("VolumeUSD", volume_usd),
+                    ("Fees", fees),
+                ]
+                .into_iter()
+                .enumerate()
+                {
+                    row.insert(format!("{}{}", kind, field), integer(value));
+                    totals[i] += value;
+                }
+                row.insert(format!("{}AverageSlip", kind), decimal(slip));
+                slips += slip;
+            }
+            for (i, field) in ["totalCount", "totalVolume", "totalVolumeUSD", "totalFees"]
+                .into_iter()
+                .enumerate()
+            {
+                row.insert(field.into(), integer(totals[i]));
+            }
+            row.insert(
+                "averageSlip".into(),
+                decimal(slips / SWAP_KINDS.len() as f64),
+            );
+            row.insert(
+                "runePriceUSD".into(),
+                decimal(ratio("", "runePriceUSD", start, 5.0)),
+            );
+            row
+        })
+        .collect();
+
+    let sums: Vec<String> = SWAP_KINDS
+        .iter()
+        .chain(&["total"])
+        .flat_map(|kind| ["Count", "Volume", "VolumeUSD", "Fees"].map(|f| format!("{}{}", kind, f)))
+        .collect();
+    let means: Vec<String> = SWAP_KINDS
+        .iter()
+        .map(|kind| format!("{}AverageSlip", kind))
+        .chain(["averageSlip".to_string()])
+        .collect();
+    let meta = aggregate(
+        window,
+        &intervals,
+        &sums.iter().map(String::as_str).collect::<Vec<_>>(),
+        &means.iter().map(String::as_str).collect::<Vec<_>>(),
+        &["runePriceUSD"],
+    );
+    response(meta, intervals)
+}
@@ -0,0 +1,334 @@
+use crate::fetch_data_from_midgard::midgard_client::Interval;
+use crate::mock_midgard::series::{self, Window, POOLS};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+
+// Ways to break the first interval of every history response, for testing
+// how the client and the data structs cope with a misbehaving Midgard
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Fault {
+    // numeric fields hold "1.2.3"
+    MalformedNumbers,
+    // numeric fields hold "", which the swap structs read as 0
+    EmptyStrings,
+    // numeric fields are left out entirely
+    MissingFields,
+}
+
+#[derive(Clone, Debug, Default, clap::Args)]
+pub struct MockOptions {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:9999")]
+    pub addr: Option<SocketAddr>,
+    /// Corrupt the first interval of every history response
+    #[arg(long, value_enum)]
+    pub fault: Option<Fault>,
+    /// Pin the clock to this unix time, for reproducible latest-page requests
+    #[arg(long)]
+    pub now: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    interval: Option<String>,
+    count: Option<u32>,
+    from: Option<i64>,
+    to: Option<i64>,
+    pool: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolsQuery {
+    status: Option<String>,
+}
+
+type MockResult = Result<Json<Value>, (StatusCode, String)>;
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, message.into())
+}
+
+fn known_pool(pool: &str) -> Result<(), (StatusCode, String)> {
+    if POOLS.iter().any(|(asset, _)| *asset == pool) {
+        Ok(())
+    } else {
+        Err((StatusCode::NOT_FOUND, format!("unknown pool {}", pool)))
+    }
+}
+
+impl MockOptions {
+    fn now(&self) -> i64 {
+        self.now.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default()
+        })
+    }
+
+    fn window(&self, query: &HistoryQuery) -> Result<Window, (StatusCode, String)> {
+        // Midgard answers a missing interval with one bucket for the whole
+        // window; the mock settles for days
+        let interval: Interval = match &query.interval {
+            Some(interval) => interval.parse().map_err(bad_request)?,
+            None => Interval::Day,
+        };
+        series::window(interval, query.count, query.from, query.to, self.now()).map_err(bad_request)
+    }
+
+    fn corrupt(&self, mut body: Value) -> Json<Value> {
+        let first = body["intervals"]
+            .as_array_mut()
+            .and_then(|intervals| intervals.first_mut())
+            .and_then(Value::as_object_mut);
+        if let (Some(fault), Some(row)) = (self.fault, first) {
+            let fields: Vec<String> = row
+                .keys()
+                .filter(|key| !matches!(key.as_str(), "startTime" | "endTime" | "pools"))
+                .cloned()
+                .collect();
+            for field in fields {
+                match fault {
+                    Fault::MalformedNumbers => row.insert(field, "1.2.3".into()),
+                    Fault::EmptyStrings => row.insert(field, "".into()),
+                    Fault::MissingFields => row.remove(&field),
+                };
+            }
+        }
+        Json(body)
+    }
+}
+
+async fn runepool(
+    State(mock): State<MockOptions>,
+    Query(query): Query<HistoryQuery>,
+) -> MockResult {
+    let window = mock.window(&query)?;
+    Ok(mock.corrupt(series::runepool(&window)))
+}
+
+async fn depths(
+    State(mock): State<MockOptions>,
+    Path(pool): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> MockResult {
+    known_pool(&pool)?;
+    let window = mock.window(&query)?;
+    Ok(mock.corrupt(series::depths(&pool, &window)))
+}
+
+async fn earnings(
+    State(mock): State<MockOptions>,
+    Query(query): Query<HistoryQuery>,
+) -> MockResult {
+    let window = mock.window(&query)?;
+    Ok(mock.corrupt(series::earnings(&window)))
+}
+
+async fn swaps(State(mock): State<MockOptions>, Query(query): Query<HistoryQuery>) -> MockResult {
+    if let Some(pool) = &query.pool {
+        known_pool(pool)?;
+    }
+    let window = mock.window(&query)?;
+    Ok(mock.corrupt(series::swaps(query.pool.as_deref(), &window)))
+}
+
+async fn pools(Query(query): Query<PoolsQuery>) -> Json<Value> {
+    let pools: Vec<Value> = POOLS
+        .iter()
+        .filter(|(_, status)| {
+            query
+                .status
+                .as_deref()
+                .is_none_or(|wanted| wanted == *status)
+        })
+        .map(|(asset, status)| serde_json::json!({ "asset": asset, "status": status }))
+        .collect();
+    Json(Value::from(pools))
+}
+
+pub fn router(options: MockOptions) -> Router {
+    Router::new()
+        .route("/v2/history/runepool", get(runepool))
+        .route("/v2/history/depths/:pool", get(depths))
+        .route("/v2/history/earnings", get(earnings))
+        .route("/v2/history/swaps", get(swaps))
+        .route("/v2/pools", get(pools))
+        .with_state(options)
+}
+
+// Serves until the process is stopped
+pub async fn serve(options: MockOptions) -> std::io::Result<()> {
+    let addr = options
+        .addr
+        .unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 9999)));
+    let listener = TcpListener::bind(addr).await?;
+    std::println!(
+        "Mock Midgard listening on http://{}",
+        listener.local_addr()?
+    );
+    axum::serve(listener, router(options)).await
+}
+
+// Serves on a free local port in the background and returns its base URL
+#[cfg(test)]
+pub async fn spawn(options: MockOptions) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router(options)).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PoolSelection, Schedule};
+    use crate::error::AppError;
+    use crate::fetch_data_from_midgard::midgard_client::{HistoryParams, MidgardClient};
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+    use crate::ingestion_pipeline::backfill::backfill;
+    use crate::ingestion_pipeline::dataset::Dataset;
+    use crate::ingestion_pipeline::ingest::{ingest_page, pools_to_ingest, IntervalFilter};
+    use crate::ingestion_pipeline::validation::OnInconsistency;
+    use sqlx::PgPool;
+
+    // 2024-05-01 00:00 UTC
+    const NOW: i64 = 1_714_521_600;
+
+    async fn client(fault: Option<Fault>) -> MidgardClient {
+        let base_url = spawn(MockOptions {
+            fault,
+            now: Some(NOW),
+            ..MockOptions::default()
+        })
+        .await;
+        let policy = RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        };
+        MidgardClient::with_policy(vec![base_url], policy, RateLimiter::new(1_000.0, 10))
+    }
+
+    #[tokio::test]
+    async fn honours_interval_count_from_and_to() {
+        let midgard = client(None).await;
+
+        let params = HistoryParams {
+            count: Some(5),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        let latest = midgard.runepool_history(&params).await.unwrap().data;
+        assert_eq!(latest.intervals.len(), 5);
+        assert_eq!(latest.meta.endTime, NOW as u128 + 3_600);
+
+        let params = HistoryParams {
+            from: Some(NOW - 3 * 86_400),
+            to: Some(NOW),
+            ..HistoryParams::new(Interval::Day)
+        };
+        let days = midgard
+            .swap_history(Some("BTC.BTC"), &params)
+            .await
+            .unwrap()
+            .data;
+        assert_eq!(days.intervals.len(), 3);
+        assert_eq!(days.meta.startTime, (NOW - 3 * 86_400) as u128);
+        assert_eq!(
+            days.meta.totalCount,
+            days.intervals.iter().map(|i| i.totalCount).sum::<u128>()
+        );
+
+        // same bucket, same numbers
+        let again = midgard
+            .swap_history(Some("BTC.BTC"), &params)
+            .await
+            .unwrap()
+            .data;
+        assert_eq!(again.meta.totalVolume, days.meta.totalVolume);
+
+        let staged = midgard.pools(Some("staged")).await.unwrap();
+        assert_eq!(staged.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn empty_strings_read_as_zero_but_bad_numbers_fail() {
+        let params = HistoryParams {
+            count: Some(2),
+            ..HistoryParams::new(Interval::Hour)
+        };
+
+        let swaps = client(Some(Fault::EmptyStrings))
+            .await
+            .swap_history(None, &params)
+            .await
+            .unwrap()
+            .data;
+        assert_eq!(swaps.intervals[0].totalCount, 0);
+        assert_ne!(swaps.intervals[1].totalCount, 0);
+
+        for fault in [Fault::MalformedNumbers, Fault::MissingFields] {
+            let error = client(Some(fault))
+                .await
+                .swap_history(None, &params)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(error, AppError::Decode(_)),
+                "{:?}: {:?}",
+                fault,
+                error
+            );
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn feeds_every_dataset_through_the_insert_scripts(db: PgPool) {
+        let midgard = client(None).await;
+        let params = HistoryParams {
+            count: Some(24),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        for dataset in Dataset::ALL {
+            for pool in pools_to_ingest(&midgard, dataset, &PoolSelection::default())
+                .await
+                .unwrap()
+            {
+                let page = ingest_page(
+                    &midgard,
+                    &db,
+                    dataset,
+                    pool.as_deref(),
+                    &params,
+                    IntervalFilter::default(),
+                    OnInconsistency::Record,
+                )
+                .await
+                .unwrap();
+                assert_eq!(page.interval_count, 24, "{:?} {:?}", dataset, pool);
+            }
+        }
+
+        // paging backwards stops at the start of the synthetic history
+        let stored = backfill(
+            &midgard,
+            &db,
+            Dataset::RunePool,
+            None,
+            &Schedule::default(),
+            0,
+            Some(series::GENESIS + 20 * 86_400),
+            series::GENESIS + 20 * 86_400,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stored.inserted, 480);
+    }
+}
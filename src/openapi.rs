@@ -0,0 +1,250 @@
+use crate::fetch_data_from_midgard::midgard_client::Interval;
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::runs::RunStatus;
+use crate::query_data_from_db::common::Order;
+use crate::query_data_from_db::{
+    gaps_query, ingestion_runs_query, midgard_history, rune_pool_data_query, rune_pool_depth_data,
+    rune_pool_earning_query, rune_pool_swap_query,
+};
+use utoipa::openapi::schema::{Object, ObjectBuilder, Type};
+use utoipa::OpenApi;
+
+// The OpenAPI 3 document of the API, served at `/openapi.json`. Schemas and
+// parameters are derived from the response structs and query extractors;
+// amounts are decimal strings, times unix seconds.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Midgard history API",
+        description = "Midgard's RUNEPool, depth, earnings and swap history, as stored by the ingestion pipeline"
+    ),
+    paths(
+        rune_pool_data_query::query_meta,
+        rune_pool_data_query::query_intervals,
+        rune_pool_depth_data::query_meta,
+        rune_pool_depth_data::query_intervals,
+        rune_pool_swap_query::fetch_meta,
+        rune_pool_swap_query::fetch_intervals,
+        rune_pool_earning_query::fetch_meta,
+        rune_pool_earning_query::fetch_intervals,
+        midgard_history::swaps,
+        midgard_history::earnings,
+        midgard_history::depths,
+        midgard_history::runepool,
+        ingestion_runs_query::list_runs,
+        gaps_query::list_gaps,
+    ),
+    // only referenced by query parameters, which are not collected
+    components(schemas(Order, Dataset, RunStatus))
+)]
+pub struct ApiDoc;
+
+// `interval` is parsed by hand rather than derived, so its values are
+// listed here
+pub fn interval() -> Object {
+    let intervals = [
+        Interval::FiveMin,
+        Interval::Hour,
+        Interval::Day,
+        Interval::Week,
+        Interval::Month,
+        Interval::Quarter,
+        Interval::Year,
+    ];
+    ObjectBuilder::new()
+        .schema_type(Type::String)
+        .enum_values(Some(intervals.map(|interval| interval.as_str())))
+        .build()
+}
+
+// `sort_by` is checked by hand as well
+pub fn swap_sort_keys() -> Object {
+    ObjectBuilder::new()
+        .schema_type(Type::String)
+        .enum_values(Some(rune_pool_swap_query::sort_keys()))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch_data_from_midgard::midgard_client::{HistoryParams, MidgardClient};
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+    use crate::ingestion_pipeline::ingest::{ingest_page, IntervalFilter};
+    use crate::ingestion_pipeline::validation::OnInconsistency;
+    use crate::mock_midgard::server::{spawn, MockOptions};
+    use serde_json::Value;
+    use sqlx::PgPool;
+    use std::collections::BTreeSet;
+    use tokio::net::TcpListener;
+
+    // axum's `:pool` is OpenAPI's `{pool}`
+    fn openapi_path(route: &str) -> String {
+        route
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn documents_every_route() {
+        let routed: BTreeSet<String> = crate::api_routes()
+            .iter()
+            .map(|(route, _)| openapi_path(route))
+            .collect();
+        let documented: BTreeSet<String> = ApiDoc::openapi().paths.paths.into_keys().collect();
+        assert_eq!(routed, documented);
+
+        // and every schema the document refers to is in it
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let text = spec.to_string();
+        for reference in text.split("\"$ref\":\"").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            let name = name.trim_start_matches("#/components/schemas/");
+            assert!(
+                spec["components"]["schemas"][name].is_object(),
+                "{} is not a schema",
+                name
+            );
+        }
+    }
+
+    // Fails naming the first value of `value` that `schema` does not describe:
+    // a key that is served but not documented or the other way round, or a
+    // value of another type
+    fn conforms(spec: &Value, schema: &Value, value: &Value, at: &str) {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            return conforms(spec, &spec["components"]["schemas"][name], value, at);
+        }
+        // Option<T> of a named T
+        if let Some(variants) = schema["oneOf"].as_array() {
+            let null = variants.iter().any(|variant| variant["type"] == "null");
+            if value.is_null() {
+                assert!(null, "{} is null but not nullable", at);
+                return;
+            }
+            let variant = variants.iter().find(|variant| variant["type"] != "null");
+            return conforms(spec, variant.unwrap(), value, at);
+        }
+
+        let types: Vec<&str> = match &schema["type"] {
+            Value::String(single) => vec![single.as_str()],
+            Value::Array(several) => several.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let served = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        let documented =
+            types.contains(&served) || (served == "integer" && types.contains(&"number"));
+        assert!(
+            types.is_empty() || documented,
+            "{} is {} but documented as {:?}",
+            at,
+            served,
+            types
+        );
+
+        if let (Some(properties), Some(object)) =
+            (schema["properties"].as_object(), value.as_object())
+        {
+            let documented: BTreeSet<&String> = properties.keys().collect();
+            let served: BTreeSet<&String> = object.keys().collect();
+            assert_eq!(served, documented, "keys of {}", at);
+            for (key, property) in properties {
+                conforms(spec, property, &object[key], &format!("{}.{}", at, key));
+            }
+        }
+        if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+            for (i, item) in array.iter().enumerate() {
+                conforms(spec, items, item, &format!("{}[{}]", at, i));
+            }
+        }
+    }
+
+    // 2024-05-02 00:00 UTC
+    const NOW: i64 = 1_714_608_000;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn serves_what_the_spec_documents(db: PgPool) {
+        let base_url = spawn(MockOptions {
+            now: Some(NOW),
+            ..MockOptions::default()
+        })
+        .await;
+        let midgard = MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        );
+        let params = HistoryParams {
+            count: Some(3),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        for (dataset, pool) in [
+            (Dataset::RunePool, None),
+            (Dataset::Depths, Some("BTC.BTC")),
+            (Dataset::Earnings, None),
+            (Dataset::Swaps, Some("BTC.BTC")),
+        ] {
+            ingest_page(
+                &midgard,
+                &db,
+                dataset,
+                pool,
+                &params,
+                IntervalFilter::default(),
+                OnInconsistency::Record,
+            )
+            .await
+            .unwrap();
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, crate::app(db)).await });
+        let http = reqwest::Client::new();
+
+        let spec: Value = http
+            .get(format!("{}/openapi.json", base))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(spec, serde_json::to_value(ApiDoc::openapi()).unwrap());
+        let docs = http.get(format!("{}/docs/", base)).send().await.unwrap();
+        assert!(docs.status().is_success());
+
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            let operation = &item["get"];
+            // every route takes the pool it has stored rows for
+            let takes_pool = operation["parameters"]
+                .as_array()
+                .is_some_and(|params| params.iter().any(|param| param["name"] == "pool"));
+            let mut url = format!("{}{}", base, path.replace("{pool}", "BTC.BTC"));
+            if takes_pool && !path.contains("{pool}") {
+                url.push_str("?pool=BTC.BTC");
+            }
+
+            let response = http.get(&url).send().await.unwrap();
+            assert_eq!(response.status(), 200, "{}", url);
+            let body: Value = response.json().await.unwrap();
+            let schema = &operation["responses"]["200"]["content"]["application/json"]["schema"];
+            conforms(&spec, schema, &body, path);
+        }
+    }
+}
@@ -1,8 +1,246 @@
-use serde::Deserialize;
+use crate::error::AppError;
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgArguments;
+use sqlx::query::QueryAs;
+use sqlx::Postgres;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Deserialize)]
+// Rows per page when `count` is not given, and the most a page can hold
+// (Midgard's own limit)
+pub const DEFAULT_COUNT: i64 = 100;
+pub const MAX_COUNT: i64 = 400;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+// `?from=&to=&count=&cursor=&order=asc|desc`, shared by every data route.
+// `from`/`to` are unix seconds and keep the rows that lie entirely inside
+// them; `start_time`/`end_time` are still accepted for the earnings routes'
+// old parameters.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct TimeRangeFilter {
-    pub start_time: Option<i64>,
-    pub end_time: Option<i64>,
-    pub count: Option<i32>,
+    #[serde(alias = "start_time")]
+    pub from: Option<i64>,
+    #[serde(alias = "end_time")]
+    pub to: Option<i64>,
+    pub count: Option<i64>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub order: Order,
+}
+
+// `?from=&to=` on the meta routes, the window meta is computed over; like
+// TimeRangeFilter it keeps the rows that lie entirely inside it
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MetaFilter {
+    #[serde(alias = "start_time")]
+    pub from: Option<i64>,
+    #[serde(alias = "end_time")]
+    pub to: Option<i64>,
+}
+
+// `?pool=BTC.BTC` on the per-pool endpoints, all pools when absent
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PoolFilter {
+    pub pool: Option<String>,
+}
+
+// One page of rows and the cursor for the next one, None on the last page
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+// A validated TimeRangeFilter. Pages are keyed on (start time, id), so the
+// cursor stays put while newer rows are ingested.
+#[derive(Clone, Copy, Debug)]
+pub struct Window {
+    from: Option<i64>,
+    to: Option<i64>,
+    after: Option<(i64, i32)>,
+    order: Order,
+    count: i64,
+}
+
+// The cursor is opaque to clients; it is the last row's `start_time:id`
+fn parse_cursor(cursor: &str) -> Option<(i64, i32)> {
+    let (start_time, id) = cursor.split_once(':')?;
+    Some((start_time.parse().ok()?, id.parse().ok()?))
+}
+
+impl TimeRangeFilter {
+    pub fn window(&self) -> Result<Window, AppError> {
+        let count = self.count.unwrap_or(DEFAULT_COUNT);
+        if !(1..=MAX_COUNT).contains(&count) {
+            return Err(AppError::Validation(format!(
+                "count must be between 1 and {}",
+                MAX_COUNT
+            )));
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if to <= from {
+                return Err(AppError::Validation("to must be after from".to_string()));
+            }
+        }
+        let after = match &self.cursor {
+            Some(cursor) => Some(
+                parse_cursor(cursor)
+                    .ok_or_else(|| AppError::Validation(format!("invalid cursor {:?}", cursor)))?,
+            ),
+            None => None,
+        };
+        Ok(Window {
+            from: self.from,
+            to: self.to,
+            after,
+            order: self.order,
+            count,
+        })
+    }
+}
+
+impl Window {
+    // Conditions on the `start`, `end` and `id` columns, to AND into a
+    // WHERE clause; they use $1 to $4, so a query's own parameters start at $6
+    pub fn conditions(&self, start: &str, end: &str, id: &str) -> String {
+        let past = match self.order {
+            Order::Asc => ">",
+            Order::Desc => "<",
+        };
+        format!(
+            "($1::bigint IS NULL OR {start} >= $1) \
+             AND ($2::bigint IS NULL OR {end} <= $2) \
+             AND ($3::bigint IS NULL OR ({start}, {id}) {past} ($3, $4::int))",
+            start = start,
+            end = end,
+            id = id,
+            past = past
+        )
+    }
+
+    // ORDER BY and LIMIT ($5) to end the query with; one row more than the
+    // page is fetched to tell whether another page follows
+    pub fn order_by(&self, start: &str, id: &str) -> String {
+        let direction = match self.order {
+            Order::Asc => "ASC",
+            Order::Desc => "DESC",
+        };
+        format!(
+            "ORDER BY {start} {direction}, {id} {direction} LIMIT $5",
+            start = start,
+            id = id,
+            direction = direction
+        )
+    }
+
+    // ORDER BY and LIMIT ($5) for a page ranked by `column` rather than in
+    // time, ties broken by `start` and `id` and unknown values last. A
+    // ranking is a single page: it takes no cursor and gives none.
+    pub fn rank_by(&self, column: &str, start: &str, id: &str) -> Result<String, AppError> {
+        if self.after.is_some() {
+            return Err(AppError::Validation(
+                "a ranked page cannot take a cursor".to_string(),
+            ));
+        }
+        let direction = match self.order {
+            Order::Asc => "ASC",
+            Order::Desc => "DESC",
+        };
+        Ok(format!(
+            "ORDER BY {column} {direction} NULLS LAST, {start} {direction}, {id} {direction} LIMIT $5",
+            column = column,
+            start = start,
+            id = id,
+            direction = direction
+        ))
+    }
+
+    // Binds $1 to $5; bind the query's own parameters after this
+    pub fn bind<'q, O>(
+        &self,
+        query: QueryAs<'q, Postgres, O, PgArguments>,
+    ) -> QueryAs<'q, Postgres, O, PgArguments> {
+        query
+            .bind(self.from)
+            .bind(self.to)
+            .bind(self.after.map(|(start_time, _)| start_time))
+            .bind(self.after.map(|(_, id)| id))
+            .bind(self.count + 1)
+    }
+
+    // Trims the extra row off `rows` and turns the last kept row's key
+    // into the next cursor
+    pub fn page<T>(&self, mut rows: Vec<T>, key: impl Fn(&T) -> (Option<i64>, i32)) -> Page<T> {
+        let more = rows.len() as i64 > self.count;
+        rows.truncate(self.count as usize);
+        let next_cursor = rows.last().filter(|_| more).map(|row| {
+            let (start_time, id) = key(row);
+            format!("{}:{}", start_time.unwrap_or_default(), id)
+        });
+        Page {
+            data: rows,
+            next_cursor,
+        }
+    }
+}
+
+// Drop-in for axum's `Query` whose rejection is the JSON error body
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, AppError> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| AppError::Validation(rejection.body_text()))?;
+        Ok(ApiQuery(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_counts_ranges_and_cursors() {
+        let filter = |query: &str| -> TimeRangeFilter {
+            let uri = format!("/?{}", query).parse().unwrap();
+            Query::try_from_uri(&uri).unwrap().0
+        };
+        assert!(filter("count=400&order=desc").window().is_ok());
+        assert!(filter("count=0").window().is_err());
+        assert!(filter("count=401").window().is_err());
+        assert!(filter("from=20&to=10").window().is_err());
+        assert!(filter("cursor=nonsense").window().is_err());
+
+        // the earnings routes' old names still work
+        let old = filter("start_time=10&end_time=20");
+        assert_eq!((old.from, old.to), (Some(10), Some(20)));
+
+        let window = filter("count=2&cursor=1714521600:7").window().unwrap();
+        let page = window.page(vec![(Some(1), 1), (Some(2), 2), (Some(3), 3)], |row| *row);
+        assert_eq!(page.data.len(), 2);
+        assert_eq!(page.next_cursor.as_deref(), Some("2:2"));
+        let last = window.page(vec![(Some(1), 1)], |row| *row);
+        assert_eq!(last.next_cursor, None);
+    }
 }
@@ -0,0 +1,70 @@
+use crate::error::{AppError, ErrorBody};
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::gaps::{find_gaps, Gap};
+use crate::query_data_from_db::common::ApiQuery;
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+
+// `?dataset=depths&pool=BTC.BTC`, both optional
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GapFilter {
+    pub dataset: Option<Dataset>,
+    pub pool: Option<String>,
+}
+
+// The gaps of one stored series
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SeriesGaps {
+    dataset: &'static str,
+    pool: Option<String>,
+    missing: i64,
+    gaps: Vec<Gap>,
+}
+
+// Read only; `repair` on the CLI or `repair_gaps` in the ingestion config
+// refetches what is listed here
+#[utoipa::path(
+    get,
+    path = "/admin/gaps",
+    tag = "admin",
+    params(GapFilter),
+    responses(
+        (status = 200, description = "Gaps per stored series", body = Vec<SeriesGaps>),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
+pub async fn list_gaps(
+    State(pool): State<PgPool>,
+    ApiQuery(filter): ApiQuery<GapFilter>,
+) -> Result<Json<Vec<SeriesGaps>>, AppError> {
+    let datasets = match filter.dataset {
+        Some(dataset) => vec![dataset],
+        None => Dataset::ALL.to_vec(),
+    };
+
+    let mut series: Vec<SeriesGaps> = Vec::new();
+    for dataset in datasets {
+        for gap in find_gaps(&pool, dataset).await? {
+            if filter.pool.is_some() && gap.pool != filter.pool {
+                continue;
+            }
+            // find_gaps orders by pool, so a series' gaps arrive together
+            match series.last_mut() {
+                Some(last) if last.dataset == dataset.as_str() && last.pool == gap.pool => {
+                    last.missing += gap.missing;
+                    last.gaps.push(gap);
+                }
+                _ => series.push(SeriesGaps {
+                    dataset: dataset.as_str(),
+                    pool: gap.pool.clone(),
+                    missing: gap.missing,
+                    gaps: vec![gap],
+                }),
+            }
+        }
+    }
+    Ok(Json(series))
+}
@@ -0,0 +1,192 @@
+use crate::error::{AppError, ErrorBody};
+use crate::ingestion_pipeline::dataset::Dataset;
+use crate::ingestion_pipeline::runs::RunStatus;
+use crate::query_data_from_db::common::ApiQuery;
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+// `?dataset=earnings&pool=BTC.BTC&status=failed&limit=20`, all optional
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RunFilter {
+    pub dataset: Option<Dataset>,
+    pub pool: Option<String>,
+    pub status: Option<RunStatus>,
+    pub limit: Option<i64>,
+}
+
+// Times are unix seconds like everywhere else in the API
+#[derive(Serialize, Debug, FromRow, ToSchema)]
+pub struct IngestionRun {
+    id: i64,
+    dataset: String,
+    pool: Option<String>,
+    kind: String,
+    requested_from: Option<i64>,
+    requested_to: Option<i64>,
+    status: String,
+    started_at: i64,
+    finished_at: Option<i64>,
+    duration_ms: Option<i64>,
+    rows_inserted: Option<i64>,
+    rows_updated: Option<i64>,
+    rows_skipped: Option<i64>,
+    error: Option<String>,
+}
+
+// The latest run of one series and when it last succeeded
+#[derive(Serialize, Debug, FromRow, ToSchema)]
+pub struct SeriesStatus {
+    dataset: String,
+    pool: Option<String>,
+    last_status: String,
+    last_started_at: i64,
+    last_succeeded_at: Option<i64>,
+    last_error: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct IngestionRuns {
+    series: Vec<SeriesStatus>,
+    runs: Vec<IngestionRun>,
+}
+
+// Per-series status first, so a stalled series stands out, then the most
+// recent runs newest first
+#[utoipa::path(
+    get,
+    path = "/admin/ingestion-runs",
+    tag = "admin",
+    params(RunFilter),
+    responses(
+        (status = 200, description = "Series status and recent runs", body = IngestionRuns),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
+pub async fn list_runs(
+    State(pool): State<PgPool>,
+    ApiQuery(filter): ApiQuery<RunFilter>,
+) -> Result<Json<IngestionRuns>, AppError> {
+    let limit = filter.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    let dataset = filter.dataset.map(|dataset| dataset.as_str());
+
+    let series = sqlx::query_as::<_, SeriesStatus>(
+        r#"
+        SELECT DISTINCT ON (dataset, pool)
+            dataset,
+            pool,
+            status AS last_status,
+            EXTRACT(EPOCH FROM started_at)::bigint AS last_started_at,
+            (
+                SELECT EXTRACT(EPOCH FROM MAX(s.finished_at))::bigint
+                FROM ingestion_runs s
+                WHERE s.dataset = r.dataset
+                    AND s.pool IS NOT DISTINCT FROM r.pool
+                    AND s.status = 'succeeded'
+            ) AS last_succeeded_at,
+            error AS last_error
+        FROM ingestion_runs r
+        WHERE ($1::text IS NULL OR dataset = $1)
+            AND ($2::text IS NULL OR pool = $2)
+        ORDER BY dataset, pool, started_at DESC
+        "#,
+    )
+    .bind(dataset)
+    .bind(&filter.pool)
+    .fetch_all(&pool)
+    .await?;
+
+    let runs = sqlx::query_as::<_, IngestionRun>(
+        r#"
+        SELECT
+            id, dataset, pool, kind, requested_from, requested_to, status,
+            EXTRACT(EPOCH FROM started_at)::bigint AS started_at,
+            EXTRACT(EPOCH FROM finished_at)::bigint AS finished_at,
+            duration_ms, rows_inserted, rows_updated, rows_skipped, error
+        FROM ingestion_runs
+        WHERE ($1::text IS NULL OR dataset = $1)
+            AND ($2::text IS NULL OR pool = $2)
+            AND ($3::text IS NULL OR status = $3)
+        ORDER BY started_at DESC, id DESC
+        LIMIT $4
+        "#,
+    )
+    .bind(dataset)
+    .bind(&filter.pool)
+    .bind(filter.status.map(|status| status.as_str()))
+    .bind(limit)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(IngestionRuns { series, runs }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingestion_pipeline::runs::{record_run, RowCounts, RunKind, RunRequest};
+
+    fn request(dataset: Dataset) -> RunRequest<'static> {
+        RunRequest {
+            dataset,
+            pool: None,
+            kind: RunKind::Poll,
+            from: Some(1_714_521_600),
+            to: Some(1_714_525_200),
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn shows_the_last_success_of_a_failing_series(db: PgPool) {
+        let stored = RowCounts {
+            inserted: 1,
+            updated: 0,
+            skipped: 1,
+        };
+        record_run(&db, request(Dataset::Earnings), async { Ok(stored) })
+            .await
+            .unwrap();
+        record_run(&db, request(Dataset::Earnings), async {
+            Err(AppError::Decode("bad payload".to_string()))
+        })
+        .await
+        .unwrap_err();
+        record_run(&db, request(Dataset::RunePool), async { Ok(stored) })
+            .await
+            .unwrap();
+
+        let filter = RunFilter {
+            dataset: Some(Dataset::Earnings),
+            pool: None,
+            status: None,
+            limit: None,
+        };
+        let Json(listing) = list_runs(State(db), ApiQuery(filter)).await.unwrap();
+
+        assert_eq!(listing.series.len(), 1);
+        let earnings = &listing.series[0];
+        assert_eq!(earnings.last_status, "failed");
+        assert!(earnings.last_succeeded_at.is_some());
+        assert!(earnings
+            .last_error
+            .as_deref()
+            .unwrap()
+            .contains("bad payload"));
+
+        assert_eq!(listing.runs.len(), 2);
+        assert_eq!(listing.runs[0].status, "failed");
+        assert_eq!(listing.runs[1].rows_inserted, Some(1));
+        assert_eq!(listing.runs[1].rows_skipped, Some(1));
+    }
+}
@@ -0,0 +1,1051 @@
+use crate::error::{AppError, ErrorBody};
+use crate::fetch_data_from_midgard::midgard_client::Interval;
+use crate::insert_data_post_migration::swap_data_insert_script::AGGREGATE_POOL;
+use crate::query_data_from_db::common::{ApiQuery, MetaFilter, PoolFilter, MAX_COUNT};
+use crate::query_data_from_db::rollup::{first, if_known, last, weighted, Rollup};
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgPool};
+use utoipa::IntoParams;
+
+// Midgard's own `/v2/history/*` parameters. With an `interval`, `count`
+// buckets are walked from `from`, back from `to`, or back from the latest
+// stored one, and `from` with `to` returns every bucket between them.
+// Without one, the whole window comes back as a single interval.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    #[param(schema_with = crate::openapi::interval)]
+    pub interval: Option<Interval>,
+    pub count: Option<i64>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+// How the stored rows are grouped into the response's intervals
+#[derive(Clone, Copy, Debug)]
+enum Buckets {
+    // one interval per stored row
+    Stored,
+    Calendar(Rollup),
+    // one interval for the whole window
+    Whole,
+}
+
+impl Buckets {
+    fn group_by(&self) -> String {
+        match self {
+            Buckets::Stored => "start_time".to_string(),
+            Buckets::Calendar(rollup) => rollup.bucket("start_time"),
+            Buckets::Whole => "()".to_string(),
+        }
+    }
+
+    fn times(&self) -> String {
+        match self {
+            Buckets::Calendar(rollup) => rollup.times("start_time"),
+            _ => "MIN(start_time) AS start_time, MAX(end_time) AS end_time".to_string(),
+        }
+    }
+
+    // The stored rows in a bucket that overlaps [$1, $2), like Midgard
+    // rounding `from` down and `to` up to whole buckets
+    fn rows_in_window(&self) -> String {
+        match self {
+            Buckets::Stored => "($1::bigint IS NULL OR end_time > $1) \
+                 AND ($2::bigint IS NULL OR start_time < $2)"
+                .to_string(),
+            Buckets::Calendar(rollup) => format!(
+                "($1::bigint IS NULL OR {bucket} >= {from}) \
+                 AND ($2::bigint IS NULL OR {bucket} < to_timestamp($2) AT TIME ZONE 'UTC')",
+                bucket = rollup.bucket("start_time"),
+                from = rollup.bucket("$1::bigint")
+            ),
+            Buckets::Whole => "($1::bigint IS NULL OR start_time >= $1) \
+                 AND ($2::bigint IS NULL OR end_time <= $2)"
+                .to_string(),
+        }
+    }
+}
+
+// A validated HistoryQuery
+#[derive(Debug)]
+struct Request {
+    buckets: Buckets,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: i64,
+    latest_first: bool,
+}
+
+impl HistoryQuery {
+    fn request(&self) -> Result<Request, AppError> {
+        if let Some(count) = self.count {
+            if !(1..=MAX_COUNT).contains(&count) {
+                return Err(AppError::Validation(format!(
+                    "count must be between 1 and {}",
+                    MAX_COUNT
+                )));
+            }
+        }
+        let bounded = self.from.is_some() && self.to.is_some();
+        if bounded && self.count.is_some() {
+            return Err(AppError::Validation(
+                "count, from and to cannot all be set".to_string(),
+            ));
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if to <= from {
+                return Err(AppError::Validation("to must be after from".to_string()));
+            }
+        }
+
+        let buckets = match self.interval {
+            None if self.count.is_some() => {
+                return Err(AppError::Validation("count needs an interval".to_string()))
+            }
+            None => Buckets::Whole,
+            Some(interval) => Rollup::of(interval)?.map_or(Buckets::Stored, Buckets::Calendar),
+        };
+        Ok(Request {
+            buckets,
+            from: self.from,
+            to: self.to,
+            // one more than Midgard allows, to tell a window that is too wide
+            limit: self.count.unwrap_or(MAX_COUNT + 1),
+            latest_first: self.from.is_none(),
+        })
+    }
+}
+
+// How a field is aggregated over the stored rows, into a bucket and into
+// meta
+#[derive(Clone, Debug)]
+enum Agg {
+    Sum,
+    First,
+    Last,
+    WeightedBy(String),
+    // meta only: a decimal expression over the `paged` rows, at Midgard's scale
+    Sql(String),
+}
+
+impl Agg {
+    fn sql(&self, column: &str) -> String {
+        match self {
+            Agg::Sum => if_known(column, &format!("SUM({})", column)),
+            Agg::First => first(column, "start_time"),
+            Agg::Last => last(column, "start_time"),
+            Agg::WeightedBy(weight) => if_known(column, &weighted(column, weight)),
+            Agg::Sql(sql) => format!("round(({}), {})", sql, DECIMAL_SCALE),
+        }
+    }
+}
+
+// How a column's value is written out
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    // whole amounts and counts, NUMERIC(39, 0) or BIGINT
+    Int,
+    // FLOAT columns: slips, node counts and the swap-side RUNE price
+    Float,
+    // NUMERIC prices, written with the digits Midgard sent, e.g. "0.0311700000"
+    Decimal,
+}
+
+// The fractional digits of Midgard's own decimals, which computed meta
+// values are rounded to
+const DECIMAL_SCALE: u32 = 10;
+
+// One camelCase key of Midgard's JSON, read from a snake_case column.
+// Midgard encodes every number as a string.
+#[derive(Clone, Debug)]
+struct Field {
+    key: String,
+    column: String,
+    agg: Agg,
+    kind: Kind,
+}
+
+fn int(key: &str, column: &str, agg: Agg) -> Field {
+    Field {
+        key: key.to_string(),
+        column: column.to_string(),
+        agg,
+        kind: Kind::Int,
+    }
+}
+
+fn float(key: &str, column: &str, agg: Agg) -> Field {
+    Field {
+        kind: Kind::Float,
+        ..int(key, column, agg)
+    }
+}
+
+fn decimal(key: &str, column: &str, agg: Agg) -> Field {
+    Field {
+        kind: Kind::Decimal,
+        ..int(key, column, agg)
+    }
+}
+
+// A meta value that is not one column's aggregate
+fn computed(key: &str, sql: String) -> Field {
+    decimal(key, "", Agg::Sql(sql))
+}
+
+// How many times `column`'s value when the window opens its last value is.
+// Like Midgard, the window opens with the snapshot of the interval just
+// before it; without one stored, with the first interval's.
+fn change(column: &str) -> String {
+    format!(
+        "(SELECT {column} FROM paged ORDER BY start_time DESC LIMIT 1) \
+         / NULLIF(COALESCE(\
+             (SELECT {column} FROM stored \
+              WHERE end_time <= (SELECT MIN(start_time) FROM paged) \
+              ORDER BY start_time DESC LIMIT 1), \
+             (SELECT {column} FROM paged ORDER BY start_time LIMIT 1)), 0)",
+        column = column
+    )
+}
+
+impl Field {
+    // The column aggregated over a bucket's stored rows
+    fn aggregate(&self) -> String {
+        format!("{} AS {}", self.agg.sql(&self.column), self.column)
+    }
+
+    // A value that is not known, e.g. a USD volume stored before Midgard's
+    // intervals were read for it, is "NaN" as in Midgard
+    fn json(&self, value: &str) -> String {
+        let text = match self.kind {
+            Kind::Int | Kind::Decimal => format!("({})::text", value),
+            Kind::Float => format!("({})::float8::text", value),
+        };
+        format!("'{}', COALESCE({}, 'NaN')", self.key, text)
+    }
+}
+
+// One dataset's history, served by its `/v2/history/*` endpoint and by the
+// meta routes
+pub struct History {
+    // the stored rows, with id, start_time, end_time and every column read
+    // by `intervals`; $4 is the pool where there is one
+    rows: &'static str,
+    intervals: Vec<Field>,
+    // aggregated over the stored rows behind the returned intervals
+    meta: Vec<Field>,
+    // earnings carry a per-pool breakdown in meta and every interval
+    pools: bool,
+}
+
+// Per-pool earnings of the stored intervals whose ids are in `ids`
+fn pool_earnings(ids: &str) -> String {
+    format!(
+        r#"'pools', COALESCE((
+            SELECT json_agg(p.totals ORDER BY p.pool)
+            FROM (
+                SELECT pool, json_build_object(
+                    'pool', pool,
+                    'assetLiquidityFees', SUM(asset_liquidity_fees)::text,
+                    'runeLiquidityFees', SUM(rune_liquidity_fees)::text,
+                    'totalLiquidityFeesRune', SUM(total_liquidity_fees_rune)::text,
+                    'saverEarning', SUM(saver_earning)::text,
+                    'rewards', SUM(rewards)::text,
+                    'earnings', SUM(earnings)::text
+                ) AS totals
+                FROM earning_data_pool_data
+                WHERE interval_id = ANY({})
+                GROUP BY pool
+            ) p
+        ), '[]'::json)"#,
+        ids
+    )
+}
+
+// camelCase to snake_case, with a run of capitals as one word:
+// startLPUnits is start_lp_units
+fn snake_case(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let mut snake = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let after_lower = !chars[i - 1].is_ascii_uppercase();
+            let ends_run = chars
+                .get(i + 1)
+                .is_some_and(|next| next.is_ascii_lowercase());
+            if after_lower || ends_run {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+impl History {
+    // The WITH clause both queries start from: `buckets` holds the stored
+    // rows of the window [$1, $2) grouped into intervals, `page` the $3 of
+    // them that are returned and `paged` the stored rows behind those
+    fn page(&self, buckets: Buckets, latest_first: bool) -> String {
+        let columns: Vec<String> = self.intervals.iter().map(Field::aggregate).collect();
+        format!(
+            r#"
+            WITH stored AS ({rows}),
+            windowed AS (SELECT *, 1 AS hours FROM stored WHERE {window}),
+            buckets AS (
+                SELECT {times}, SUM(hours) AS hours, array_agg(id) AS ids, {columns}
+                FROM windowed
+                GROUP BY {group_by}
+                HAVING COUNT(*) > 0
+            ),
+            page AS (SELECT * FROM buckets ORDER BY start_time {direction} LIMIT $3),
+            paged AS (
+                SELECT * FROM windowed WHERE id = ANY(ARRAY(SELECT unnest(ids) FROM page))
+            )
+            "#,
+            rows = self.rows,
+            window = buckets.rows_in_window(),
+            times = buckets.times(),
+            columns = columns.join(", "),
+            group_by = buckets.group_by(),
+            direction = if latest_first { "DESC" } else { "ASC" }
+        )
+    }
+
+    // The whole response, built as JSON in one query
+    async fn fetch(
+        &self,
+        db: &PgPool,
+        query: &HistoryQuery,
+        pool: Option<&str>,
+    ) -> Result<Json<Value>, AppError> {
+        let request = query.request()?;
+
+        let mut interval: Vec<String> = self
+            .intervals
+            .iter()
+            .map(|field| field.json(&field.column))
+            .collect();
+        let mut meta: Vec<String> = self
+            .meta
+            .iter()
+            .map(|field| field.json(&field.agg.sql(&field.column)))
+            .collect();
+        if self.pools {
+            interval.push(pool_earnings("ids"));
+            meta.push(pool_earnings("ARRAY(SELECT id FROM paged)"));
+        }
+
+        let sql = format!(
+            r#"
+            {page}
+            SELECT json_build_object(
+                'meta', (
+                    SELECT json_build_object(
+                        'startTime', COALESCE((SELECT MIN(start_time) FROM page), $1, 0)::text,
+                        'endTime', COALESCE((SELECT MAX(end_time) FROM page), $2, 0)::text,
+                        {meta}
+                    )
+                    FROM paged
+                ),
+                'intervals', COALESCE((
+                    SELECT json_agg(json_build_object(
+                        'startTime', start_time::text,
+                        'endTime', end_time::text,
+                        {interval}
+                    ) ORDER BY start_time)
+                    FROM page
+                ), '[]'::json)
+            )::text,
+            (SELECT COUNT(*) FROM page)
+            "#,
+            page = self.page(request.buckets, request.latest_first),
+            meta = meta.join(", "),
+            interval = interval.join(", ")
+        );
+
+        let mut statement = sqlx::query_as::<_, (String, i64)>(&sql)
+            .bind(request.from)
+            .bind(request.to)
+            .bind(request.limit);
+        if let Some(pool) = pool {
+            statement = statement.bind(pool);
+        }
+        let (body, returned) = statement.fetch_one(db).await?;
+
+        if returned > MAX_COUNT {
+            return Err(AppError::Validation(format!(
+                "the window spans more than {} intervals",
+                MAX_COUNT
+            )));
+        }
+        let body = serde_json::from_str(&body).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        Ok(Json(body))
+    }
+
+    // Meta over the stored rows that lie entirely inside [from, to), as
+    // one row: start_time, end_time, pool when there is one, and every meta
+    // field under its snake_case key. Values are NULL when nothing is stored
+    // in the window.
+    pub async fn window_meta<T>(
+        &self,
+        db: &PgPool,
+        window: &MetaFilter,
+        pool: Option<&str>,
+    ) -> Result<T, AppError>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let query = HistoryQuery {
+            from: window.from,
+            to: window.to,
+            ..HistoryQuery::default()
+        };
+        let request = query.request()?;
+
+        let mut columns: Vec<String> = self
+            .meta
+            .iter()
+            .map(|field| {
+                let cast = match field.kind {
+                    Kind::Float => "float8",
+                    Kind::Int | Kind::Decimal => "numeric",
+                };
+                format!(
+                    "({})::{} AS {}",
+                    field.agg.sql(&field.column),
+                    cast,
+                    snake_case(&field.key)
+                )
+            })
+            .collect();
+        if pool.is_some() {
+            columns.push("$4::text AS pool".to_string());
+        }
+        let sql = format!(
+            r#"
+            {page}
+            SELECT
+                COALESCE((SELECT MIN(start_time) FROM page), $1) AS start_time,
+                COALESCE((SELECT MAX(end_time) FROM page), $2) AS end_time,
+                {columns}
+            FROM paged
+            "#,
+            page = self.page(request.buckets, false),
+            columns = columns.join(", ")
+        );
+
+        let mut statement = sqlx::query_as::<_, T>(&sql)
+            .bind(request.from)
+            .bind(request.to)
+            .bind(1_i64);
+        if let Some(pool) = pool {
+            statement = statement.bind(pool);
+        }
+        Ok(statement.fetch_one(db).await?)
+    }
+}
+
+// The directions of a swap, as in Midgard's keys and in the column names
+pub const SWAP_KINDS: [(&str, &str); 6] = [
+    ("toAsset", "to_asset"),
+    ("toRune", "to_rune"),
+    ("toTrade", "to_trade"),
+    ("fromTrade", "from_trade"),
+    ("synthMint", "synth_mint"),
+    ("synthRedeem", "synth_redeem"),
+];
+
+// Totals add up; slips and the price are averaged weighted by the volume
+// they were measured on, as in the `/swapData/intervals` rollups
+fn swap_fields() -> Vec<Field> {
+    let mut fields = Vec::new();
+    for (key, column) in SWAP_KINDS.iter().chain(&[("total", "total")]) {
+        for (key_suffix, column_suffix) in [
+            ("Count", "count"),
+            ("Volume", "volume"),
+            ("VolumeUSD", "volume_usd"),
+            ("Fees", "fees"),
+        ] {
+            fields.push(int(
+                &format!("{}{}", key, key_suffix),
+                &format!("{}_{}", column, column_suffix),
+                Agg::Sum,
+            ));
+        }
+    }
+    for (key, column) in SWAP_KINDS {
+        fields.push(float(
+            &format!("{}AverageSlip", key),
+            &format!("{}_average_slip", column),
+            Agg::WeightedBy(format!("{}_volume", column)),
+        ));
+    }
+    for (key, column) in [
+        ("averageSlip", "average_slip"),
+        ("runePriceUSD", "rune_price_usd"),
+    ] {
+        fields.push(float(
+            key,
+            column,
+            Agg::WeightedBy("total_volume".to_string()),
+        ));
+    }
+    fields
+}
+
+// Every swap column aggregated over a bucket's stored rows, for rollups
+// that combine swap intervals the way `/v2/history/swaps` does
+pub fn swap_aggregates() -> Vec<String> {
+    swap_fields().iter().map(Field::aggregate).collect()
+}
+
+impl History {
+    pub fn swaps() -> History {
+        let fields = swap_fields();
+        History {
+            rows: "SELECT * FROM swap_data_rune_pool_interval WHERE pool = $4",
+            intervals: fields.clone(),
+            meta: fields,
+            pools: false,
+        }
+    }
+
+    // Every depth is a snapshot at the end of its interval; meta compares the
+    // first stored interval with the last
+    pub fn depths() -> History {
+        let snapshots = [
+            ("assetDepth", "asset_depth", false),
+            ("runeDepth", "rune_depth", false),
+            ("assetPrice", "asset_price", true),
+            ("assetPriceUSD", "asset_price_usd", true),
+            ("liquidityUnits", "liquidity_units", false),
+            ("membersCount", "members_count", false),
+            ("synthUnits", "synth_units", false),
+            ("synthSupply", "synth_supply", false),
+            ("units", "units", false),
+            ("luvi", "luvi", true),
+        ];
+        let intervals = snapshots
+            .iter()
+            .map(|&(key, column, is_decimal)| match is_decimal {
+                true => decimal(key, column, Agg::Last),
+                false => int(key, column, Agg::Last),
+            })
+            .collect();
+
+        let mut meta = Vec::new();
+        for (prefix, agg) in [("start", Agg::First), ("end", Agg::Last)] {
+            for (key, column) in [
+                ("AssetDepth", "asset_depth"),
+                ("RuneDepth", "rune_depth"),
+                ("LPUnits", "liquidity_units"),
+                ("MemberCount", "members_count"),
+                ("SynthUnits", "synth_units"),
+            ] {
+                meta.push(int(&format!("{}{}", prefix, key), column, agg.clone()));
+            }
+        }
+        // an LP's value against holding, for the price move over the window:
+        // 2 * sqrt(r) / (1 + r), and the luvi's growth over it
+        meta.push(computed(
+            "priceShiftLoss",
+            format!(
+                "SELECT 2 * sqrt(ratio) / (1 + ratio) FROM (SELECT {} AS ratio) r",
+                change("asset_price")
+            ),
+        ));
+        meta.push(computed("luviIncrease", change("luvi")));
+
+        History {
+            rows: r#"
+                SELECT
+                    id,
+                    startTime AS start_time,
+                    endTime AS end_time,
+                    assetDepth AS asset_depth,
+                    runeDepth AS rune_depth,
+                    assetPrice AS asset_price,
+                    assetPriceUSD AS asset_price_usd,
+                    liquidityUnits AS liquidity_units,
+                    membersCount AS members_count,
+                    synthUnits AS synth_units,
+                    synthSupply AS synth_supply,
+                    units,
+                    luvi
+                FROM Rune_Pool_Depth_Intervals
+                WHERE pool = $4
+                "#,
+            intervals,
+            meta,
+            pools: false,
+        }
+    }
+
+    pub fn earnings() -> History {
+        let mut fields: Vec<Field> = [
+            ("liquidityFees", "liquidity_fees"),
+            ("blockRewards", "block_rewards"),
+            ("earnings", "earnings"),
+            ("bondingEarnings", "bonding_earnings"),
+            ("liquidityEarnings", "liquidity_earnings"),
+        ]
+        .iter()
+        .map(|&(key, column)| int(key, column, Agg::Sum))
+        .collect();
+        // the node count is averaged over time, i.e. over the stored hours
+        fields.push(float(
+            "avgNodeCount",
+            "avg_node_count",
+            Agg::WeightedBy("hours".to_string()),
+        ));
+        fields.push(float("runePriceUSD", "rune_price_usd", Agg::Last));
+
+        History {
+            rows: "SELECT id, start_time, end_time, liquidity_fees, block_rewards, earnings, \
+                   bonding_earnings, liquidity_earnings, avg_node_count, rune_price_usd \
+                   FROM earning_data_rune_pool_interval",
+            intervals: fields.clone(),
+            meta: fields,
+            pools: true,
+        }
+    }
+
+    pub fn runepool() -> History {
+        History {
+            rows: "SELECT id, startTime AS start_time, endTime AS end_time, count, units \
+                   FROM Rune_Pool_Data_Intervals",
+            intervals: vec![
+                int("count", "count", Agg::Last),
+                int("units", "units", Agg::Last),
+            ],
+            meta: vec![
+                int("startCount", "count", Agg::First),
+                int("startUnits", "units", Agg::First),
+                int("endCount", "count", Agg::Last),
+                int("endUnits", "units", Agg::Last),
+            ],
+            pools: false,
+        }
+    }
+}
+
+// Without `?pool=` the all-pools series, like Midgard
+#[utoipa::path(
+    get,
+    path = "/v2/history/swaps",
+    tag = "midgard",
+    params(PoolFilter, HistoryQuery),
+    responses(
+        (status = 200, description = "Midgard's swap history", body = Object),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
+pub async fn swaps(
+    State(db): State<PgPool>,
+    ApiQuery(filter): ApiQuery<PoolFilter>,
+    ApiQuery(query): ApiQuery<HistoryQuery>,
+) -> Result<Json<Value>, AppError> {
+    let pool = filter.pool.unwrap_or_else(|| AGGREGATE_POOL.to_string());
+    History::swaps().fetch(&db, &query, Some(&pool)).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v2/history/depths/{pool}",
+    tag = "midgard",
+    params(("pool" = String, Path, description = "e.g. BTC.BTC"), HistoryQuery),
+    responses(
+        (status = 200, description = "Midgard's depth history of the pool", body = Object),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+        (status = 404, description = "No depths are stored for the pool", body = ErrorBody),
+    )
+)]
+pub async fn depths(
+    State(db): State<PgPool>,
+    Path(pool): Path<String>,
+    ApiQuery(query): ApiQuery<HistoryQuery>,
+) -> Result<Json<Value>, AppError> {
+    // Midgard answers an unknown pool with a 404, not an empty history
+    let known: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM Rune_Pool_Depth_Intervals WHERE pool = $1)",
+    )
+    .bind(&pool)
+    .fetch_one(&db)
+    .await?;
+    if !known {
+        return Err(AppError::NotFound(format!("pool {}", pool)));
+    }
+    History::depths().fetch(&db, &query, Some(&pool)).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v2/history/earnings",
+    tag = "midgard",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "Midgard's earnings history", body = Object),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
+pub async fn earnings(
+    State(db): State<PgPool>,
+    ApiQuery(query): ApiQuery<HistoryQuery>,
+) -> Result<Json<Value>, AppError> {
+    History::earnings().fetch(&db, &query, None).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v2/history/runepool",
+    tag = "midgard",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "Midgard's RUNEPool history", body = Object),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
+pub async fn runepool(
+    State(db): State<PgPool>,
+    ApiQuery(query): ApiQuery<HistoryQuery>,
+) -> Result<Json<Value>, AppError> {
+    History::runepool().fetch(&db, &query, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structs::{depth_data, earning_history, swap_history};
+    use crate::fetch_data_from_midgard::midgard_client::{HistoryParams, MidgardClient};
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+    use crate::ingestion_pipeline::dataset::Dataset;
+    use crate::ingestion_pipeline::ingest::{ingest_page, IntervalFilter};
+    use crate::ingestion_pipeline::validation::{
+        check_depths, check_earnings, check_swaps, OnInconsistency,
+    };
+    use crate::mock_midgard::server::{spawn, MockOptions};
+    use crate::query_data_from_db::common::TimeRangeFilter;
+    use crate::query_data_from_db::rollup::RollupFilter;
+    use crate::query_data_from_db::rune_pool_swap_query::{
+        fetch_intervals, fetch_meta, SortFilter,
+    };
+
+    // 2024-05-02 00:00 UTC
+    const NOW: i64 = 1_714_608_000;
+
+    // the last full day before NOW
+    fn yesterday(interval: Option<Interval>) -> ApiQuery<HistoryQuery> {
+        ApiQuery(HistoryQuery {
+            interval,
+            from: Some(NOW - 86_400),
+            to: Some(NOW),
+            ..HistoryQuery::default()
+        })
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn serves_stored_history_in_midgards_shape(db: PgPool) {
+        let base_url = spawn(MockOptions {
+            now: Some(NOW),
+            ..MockOptions::default()
+        })
+        .await;
+        let midgard = MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        );
+        let params = HistoryParams {
+            count: Some(48),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        for (dataset, pool) in [
+            (Dataset::Swaps, None),
+            (Dataset::Depths, Some("BTC.BTC")),
+            (Dataset::Earnings, None),
+        ] {
+            ingest_page(
+                &midgard,
+                &db,
+                dataset,
+                pool,
+                &params,
+                IntervalFilter::default(),
+                OnInconsistency::Record,
+            )
+            .await
+            .unwrap();
+        }
+
+        // what Midgard itself says about that day
+        let last_day = HistoryParams {
+            from: Some(NOW - 86_400),
+            to: Some(NOW),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        let upstream = midgard.swap_history(None, &last_day).await.unwrap().data;
+
+        let Json(body) = swaps(
+            State(db.clone()),
+            ApiQuery(PoolFilter { pool: None }),
+            yesterday(Some(Interval::Hour)),
+        )
+        .await
+        .unwrap();
+        let served: swap_history::RootSwapDetails =
+            serde_json::from_str(&body.to_string()).unwrap();
+        assert_eq!(served.intervals.len(), 24);
+        assert_eq!(check_swaps(&served), Vec::new());
+        assert_eq!(served.meta.startTime, upstream.meta.startTime);
+        assert_eq!(served.meta.totalCount, upstream.meta.totalCount);
+        assert_eq!(served.meta.totalFees, upstream.meta.totalFees);
+        assert_eq!(
+            served.intervals[23].synthRedeemVolumeUSD,
+            upstream.intervals[23].synthRedeemVolumeUSD
+        );
+
+        // a day is its 24 hours
+        let Json(day) = swaps(
+            State(db.clone()),
+            ApiQuery(PoolFilter { pool: None }),
+            yesterday(Some(Interval::Day)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(day["intervals"][0]["startTime"], (NOW - 86_400).to_string());
+        assert_eq!(
+            day["meta"]["totalCount"],
+            served.meta.totalCount.to_string()
+        );
+
+        // the meta route computes the same meta over any window
+        let Json(meta) = fetch_meta(
+            State(db.clone()),
+            ApiQuery(PoolFilter { pool: None }),
+            ApiQuery(MetaFilter {
+                from: Some(NOW - 86_400),
+                to: Some(NOW),
+            }),
+        )
+        .await
+        .unwrap();
+        let meta = serde_json::to_value(meta).unwrap();
+        assert_eq!(meta["start_time"], NOW - 86_400);
+        assert_eq!(meta["total_count"], served.meta.totalCount.to_string());
+        assert_eq!(meta["total_fees"], served.meta.totalFees.to_string());
+
+        let Json(body) = depths(
+            State(db.clone()),
+            Path("BTC.BTC".to_string()),
+            yesterday(Some(Interval::Hour)),
+        )
+        .await
+        .unwrap();
+        let served: depth_data::RootDepthDetails = serde_json::from_str(&body.to_string()).unwrap();
+        assert_eq!(check_depths(&served), Vec::new());
+        // decimal prices come back as Midgard wrote them, trailing zeros and all
+        let upstream = midgard.depth_history("BTC.BTC", &last_day).await.unwrap();
+        let upstream: Value = serde_json::from_str(&upstream.raw.body).unwrap();
+        for key in ["assetPrice", "assetPriceUSD", "luvi"] {
+            assert_eq!(body["intervals"][23][key], upstream["intervals"][23][key]);
+        }
+        // measured from the hour before the window, which is stored too
+        for key in ["priceShiftLoss", "luviIncrease"] {
+            assert_eq!(body["meta"][key], upstream["meta"][key]);
+        }
+
+        // no interval: the whole window as one
+        let Json(body) = earnings(State(db), yesterday(None)).await.unwrap();
+        let served: earning_history::RootEarnDetails =
+            serde_json::from_str(&body.to_string()).unwrap();
+        assert_eq!(served.intervals.len(), 1);
+        assert_eq!(check_earnings(&served), Vec::new());
+        assert_eq!(body["meta"]["pools"].as_array().unwrap().len(), 2);
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn unknown_values_are_nan_and_keep_their_bucket_unknown(db: PgPool) {
+        let base_url = spawn(MockOptions {
+            now: Some(NOW),
+            ..MockOptions::default()
+        })
+        .await;
+        let midgard = MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        );
+        let params = HistoryParams {
+            count: Some(48),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        ingest_page(
+            &midgard,
+            &db,
+            Dataset::Swaps,
+            None,
+            &params,
+            IntervalFilter::default(),
+            OnInconsistency::Record,
+        )
+        .await
+        .unwrap();
+        // the last hour as stored before Midgard's intervals were read for
+        // their USD volumes
+        sqlx::query(
+            "UPDATE swap_data_rune_pool_interval \
+             SET total_volume_usd = NULL, synth_mint_average_slip = NULL WHERE start_time = $1",
+        )
+        .bind(NOW - 3_600)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let Json(hours) = swaps(
+            State(db.clone()),
+            ApiQuery(PoolFilter { pool: None }),
+            yesterday(Some(Interval::Hour)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(hours["intervals"][23]["totalVolumeUSD"], "NaN");
+        assert_eq!(hours["intervals"][23]["synthMintAverageSlip"], "NaN");
+        assert_ne!(hours["intervals"][22]["totalVolumeUSD"], "NaN");
+        assert_eq!(hours["meta"]["totalVolumeUSD"], "NaN");
+        assert_eq!(hours["meta"]["synthMintAverageSlip"], "NaN");
+        assert_ne!(hours["meta"]["totalVolume"], "NaN");
+
+        // nor is a day with that hour in it
+        let Json(day) = swaps(
+            State(db.clone()),
+            ApiQuery(PoolFilter { pool: None }),
+            yesterday(Some(Interval::Day)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(day["intervals"][0]["totalVolumeUSD"], "NaN");
+        assert_eq!(day["intervals"][0]["synthMintAverageSlip"], "NaN");
+        assert_eq!(
+            day["intervals"][0]["totalVolume"],
+            hours["meta"]["totalVolume"]
+        );
+        let Json(days) = fetch_intervals(
+            State(db),
+            ApiQuery(PoolFilter { pool: None }),
+            ApiQuery(TimeRangeFilter {
+                from: Some(NOW - 86_400),
+                ..TimeRangeFilter::default()
+            }),
+            ApiQuery(RollupFilter {
+                interval: Some(Interval::Day),
+            }),
+            ApiQuery(SortFilter { sort_by: None }),
+        )
+        .await
+        .unwrap();
+        let days = serde_json::to_value(&days.data).unwrap();
+        assert_eq!(days[0]["total_volume_usd"], Value::Null);
+        assert_eq!(days[0]["synth_mint_average_slip"], Value::Null);
+        assert_eq!(days[0]["total_volume"], hours["meta"]["totalVolume"]);
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn unknown_depth_pool_is_not_found(db: PgPool) {
+        let error = depths(
+            State(db),
+            Path("DOGE.DOGE".to_string()),
+            yesterday(Some(Interval::Hour)),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, AppError::NotFound(ref what) if what == "pool DOGE.DOGE"));
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn rolls_swaps_up_like_the_swap_data_route(db: PgPool) {
+        let base_url = spawn(MockOptions {
+            now: Some(NOW),
+            ..MockOptions::default()
+        })
+        .await;
+        let midgard = MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        );
+        let params = HistoryParams {
+            count: Some(48),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        ingest_page(
+            &midgard,
+            &db,
+            Dataset::Swaps,
+            None,
+            &params,
+            IntervalFilter::default(),
+            OnInconsistency::Record,
+        )
+        .await
+        .unwrap();
+
+        let Json(rolled) = fetch_intervals(
+            State(db.clone()),
+            ApiQuery(PoolFilter { pool: None }),
+            ApiQuery(TimeRangeFilter {
+                from: Some(NOW - 2 * 86_400),
+                to: Some(NOW),
+                ..TimeRangeFilter::default()
+            }),
+            ApiQuery(RollupFilter {
+                interval: Some(Interval::Day),
+            }),
+            ApiQuery(SortFilter { sort_by: None }),
+        )
+        .await
+        .unwrap();
+        let rolled = serde_json::to_value(&rolled.data).unwrap();
+        let Json(history) = swaps(
+            State(db),
+            ApiQuery(PoolFilter { pool: None }),
+            ApiQuery(HistoryQuery {
+                interval: Some(Interval::Day),
+                from: Some(NOW - 2 * 86_400),
+                to: Some(NOW),
+                ..HistoryQuery::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        let days = history["intervals"].as_array().unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(rolled.as_array().unwrap().len(), days.len());
+        for (rolled, day) in rolled.as_array().unwrap().iter().zip(days) {
+            assert_eq!(rolled["start_time"].to_string(), day["startTime"]);
+            for (column, key) in [
+                ("total_count", "totalCount"),
+                ("total_volume", "totalVolume"),
+                ("to_asset_average_slip", "toAssetAverageSlip"),
+                ("to_rune_average_slip", "toRuneAverageSlip"),
+                ("average_slip", "averageSlip"),
+                ("rune_price_usd", "runePriceUSD"),
+            ] {
+                let served = match &rolled[column] {
+                    Value::String(text) => text.clone(),
+                    number => number.to_string(),
+                };
+                let served: f64 = served.parse().unwrap();
+                let history: f64 = day[key].as_str().unwrap().parse().unwrap();
+                assert_eq!(served, history, "{} of {}", key, day["startTime"]);
+            }
+        }
+    }
+}
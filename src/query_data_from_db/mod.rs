@@ -1,5 +1,9 @@
+pub mod common;
+pub mod gaps_query;
+pub mod ingestion_runs_query;
+pub mod midgard_history;
+pub mod rollup;
 pub mod rune_pool_data_query;
 pub mod rune_pool_depth_data;
-pub mod rune_pool_earnings_query;
-pub mod rune_pool_swap_query;
 pub mod rune_pool_earning_query;
+pub mod rune_pool_swap_query;
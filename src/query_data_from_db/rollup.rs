@@ -0,0 +1,196 @@
+use crate::error::AppError;
+use crate::fetch_data_from_midgard::midgard_client::Interval;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+// `?interval=day` on the `/intervals` routes. Without it, or with the
+// stored `hour`, rows come back as stored.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RollupFilter {
+    #[param(schema_with = crate::openapi::interval)]
+    pub interval: Option<Interval>,
+}
+
+// Aggregates the stored hourly rows into calendar buckets (UTC, weeks
+// starting on Monday), with a rule per kind of column:
+// - counts, volumes, fees and earnings add up
+// - depths, units, member counts and point-in-time prices take the bucket's
+//   last value, like Midgard's own depth history
+// - slips and swap prices are weighted by the volume they were measured on
+//
+// A bucket's `id` is the lowest of its rows' ids, so the (start_time, id)
+// cursor keeps working on rolled up pages.
+#[derive(Clone, Copy, Debug)]
+pub struct Rollup {
+    unit: &'static str,
+}
+
+impl RollupFilter {
+    pub fn rollup(&self) -> Result<Option<Rollup>, AppError> {
+        self.interval.map_or(Ok(None), Rollup::of)
+    }
+}
+
+impl Rollup {
+    // None for the stored `hour`, which needs no rollup
+    pub fn of(interval: Interval) -> Result<Option<Rollup>, AppError> {
+        let unit = match interval {
+            Interval::Hour => return Ok(None),
+            Interval::FiveMin => {
+                return Err(AppError::Validation(
+                    "interval 5min is finer than the stored hourly rows".to_string(),
+                ))
+            }
+            Interval::Day => "day",
+            Interval::Week => "week",
+            Interval::Month => "month",
+            Interval::Quarter => "quarter",
+            Interval::Year => "year",
+        };
+        Ok(Some(Rollup { unit }))
+    }
+
+    // The bucket a row falls in, by its `start` column (unix seconds)
+    pub fn bucket(&self, start: &str) -> String {
+        format!(
+            "date_trunc('{}', to_timestamp({}) AT TIME ZONE 'UTC')",
+            self.unit, start
+        )
+    }
+
+    // The bucket's own start and end as unix seconds; the end is the next
+    // bucket's start, also for the bucket still being filled
+    pub fn times(&self, start: &str) -> String {
+        let bucket = self.bucket(start);
+        format!(
+            "EXTRACT(EPOCH FROM {bucket})::bigint AS start_time, \
+             EXTRACT(EPOCH FROM {bucket} + interval '1 {unit}')::bigint AS end_time",
+            bucket = bucket,
+            unit = self.unit
+        )
+    }
+}
+
+// The value of `column` in the bucket's latest row
+pub fn last(column: &str, start: &str) -> String {
+    format!("(array_agg({} ORDER BY {} DESC))[1]", column, start)
+}
+
+// The value of `column` in the bucket's earliest row
+pub fn first(column: &str, start: &str) -> String {
+    format!("(array_agg({} ORDER BY {}))[1]", column, start)
+}
+
+// `column` averaged with `weight` as the weights; a bucket without any
+// weight falls back to the plain average
+pub fn weighted(column: &str, weight: &str) -> String {
+    format!(
+        "COALESCE(SUM({column} * {weight}::float8) / NULLIF(SUM({weight}::float8), 0), AVG({column}))",
+        column = column,
+        weight = weight
+    )
+}
+
+// `aggregate` of `column`, or NULL when the column is not known for some of
+// the bucket's rows: a sum or average over the rest would pass for the whole
+pub fn if_known(column: &str, aggregate: &str) -> String {
+    format!(
+        "CASE WHEN COUNT(*) = COUNT({}) THEN {} END",
+        column, aggregate
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::insert_data_post_migration::swap_data_insert_script;
+    use crate::query_data_from_db::common::{ApiQuery, PoolFilter, TimeRangeFilter};
+    use crate::query_data_from_db::rune_pool_swap_query::{fetch_intervals, SortFilter};
+    use axum::extract::State;
+    use axum::Json;
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn rolls_hours_up_into_days(db: PgPool) {
+        // 30 hours from 2024-05-01 00:00 UTC: a full day and 6 hours of the next
+        let day = 1_714_521_600;
+        for hour in 0..30_i64 {
+            let start = day + hour * 3_600;
+            // the swap count moves against the volume, so weighting by
+            // count would give other averages
+            let (count, volume, slip) = if hour % 24 < 12 {
+                (3, 1_000, 1.0)
+            } else {
+                (1, 3_000, 5.0)
+            };
+            let mut row = serde_json::json!({
+                "startTime": start.to_string(), "endTime": (start + 3_600).to_string(),
+                "toAssetCount": count.to_string(), "toRuneCount": "0", "toTradeCount": "0",
+                "fromTradeCount": "0", "synthMintCount": "0", "synthRedeemCount": "0",
+                "totalCount": count.to_string(),
+                "toAssetVolume": volume.to_string(), "toRuneVolume": "0",
+                "toTradeVolume": "0", "fromTradeVolume": "0",
+                "synthMintVolume": "0", "synthRedeemVolume": "0",
+                "totalVolume": volume.to_string(),
+                "toAssetAverageSlip": slip.to_string(), "toRuneAverageSlip": "0",
+                "toTradeAverageSlip": "0", "fromTradeAverageSlip": "0",
+                "synthMintAverageSlip": "0", "synthRedeemAverageSlip": "0",
+                "averageSlip": slip.to_string(), "runePriceUSD": (hour as f64).to_string(),
+            });
+            // USD volumes and fees play no part in the rollup
+            for kind in [
+                "toAsset",
+                "toRune",
+                "toTrade",
+                "fromTrade",
+                "synthMint",
+                "synthRedeem",
+                "total",
+            ] {
+                row[format!("{}VolumeUSD", kind).as_str()] = "0".into();
+                row[format!("{}Fees", kind).as_str()] = "0".into();
+            }
+            let interval: crate::data_structs::swap_history::RunePoolInterval =
+                serde_json::from_str(&row.to_string()).unwrap();
+            swap_data_insert_script::insert_rune_pool_intervals(
+                &[interval],
+                swap_data_insert_script::AGGREGATE_POOL,
+                None,
+                &db,
+            )
+            .await
+            .unwrap();
+        }
+
+        let rollup = RollupFilter {
+            interval: Some(Interval::Day),
+        };
+        let Json(page) = fetch_intervals(
+            State(db),
+            ApiQuery(PoolFilter { pool: None }),
+            ApiQuery(TimeRangeFilter::default()),
+            ApiQuery(rollup),
+            ApiQuery(SortFilter { sort_by: None }),
+        )
+        .await
+        .unwrap();
+        let days = serde_json::to_value(&page.data).unwrap();
+
+        assert_eq!(days.as_array().unwrap().len(), 2);
+        assert_eq!(days[0]["start_time"], day);
+        assert_eq!(days[0]["end_time"], day + 86_400);
+        assert_eq!(days[0]["total_count"], "48");
+        assert_eq!(days[1]["total_volume"], "6000");
+        assert_eq!(days[1]["end_time"], day + 2 * 86_400);
+        // 12 hours of 1.0 on 1000 and 12 of 5.0 on 3000: 4.0, where the plain
+        // average is 3.0 and the count-weighted one 2.0
+        assert_eq!(days[0]["average_slip"], 4.0);
+        assert_eq!(days[0]["to_asset_average_slip"], 4.0);
+        // hours 0-11 on 1000 and 12-23 on 3000: 14.5, not the last hour's 23.0
+        // or the count-weighted 8.5
+        assert_eq!(days[0]["rune_price_usd"], 14.5);
+        // the second day's hours all carry the same volume
+        assert_eq!(days[1]["rune_price_usd"], 26.5);
+    }
+}
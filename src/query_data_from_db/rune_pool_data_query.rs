@@ -1,162 +1,107 @@
+use crate::error::{AppError, ErrorBody};
+use crate::query_data_from_db::common::{ApiQuery, MetaFilter, Page, TimeRangeFilter};
+use crate::query_data_from_db::midgard_history::History;
+use crate::query_data_from_db::rollup::{last, RollupFilter};
 use axum::{extract::State, Json};
-use serde::{Deserialize, Serialize};
+use bigdecimal::BigDecimal;
+use serde::Serialize;
 use sqlx::PgPool;
+use utoipa::ToSchema;
 
-#[derive(Serialize, sqlx::FromRow)]
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
 pub struct RunePoolMeta {
-    id: i32,
     start_time: Option<i64>,
     end_time: Option<i64>,
-    start_units: Option<i64>,
-    start_count: Option<i64>,
-    end_units: Option<i64>,
-    end_count: Option<i64>,
+    #[schema(value_type = Option<String>)]
+    start_units: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    start_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    end_units: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    end_count: Option<BigDecimal>,
 }
 
-#[derive(Serialize, sqlx::FromRow)]
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
 pub struct RunePoolIntervals {
     id: i32,
     start_time: Option<i64>,
     end_time: Option<i64>,
-    count: Option<i64>,
-    units: Option<i64>,
-}
-
-#[derive(Deserialize)]
-pub struct IntervalQueryParams {
-    interval: Option<String>,
-    count: Option<u32>,
-    to: Option<i64>,
+    #[schema(value_type = Option<String>)]
+    count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    units: Option<BigDecimal>,
 }
 
-// pub async fn start_server(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
-//     let app = Router::new()
-//         .route("/meta", get(query_meta))
-//         .route("/intervals", get(query_intervals))
-//         .with_state(pool);
-
-//     let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
-//     println!("API Server running at http://{}", addr);
-
-//     let listener = TcpListener::bind(addr).await?;
-//     axum::serve(listener, app).await?;
-
-//     Ok(())
-// }
-
+// Computed from the stored intervals of the window
+#[utoipa::path(
+    get,
+    path = "/runepooldata/meta",
+    tag = "runepool",
+    params(MetaFilter),
+    responses(
+        (status = 200, description = "Meta of the window", body = RunePoolMeta),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
 pub async fn query_meta(
     State(pool): State<PgPool>,
-) -> Result<Json<Vec<RunePoolMeta>>, (axum::http::StatusCode, String)> {
-    let rows = sqlx::query_as!(
-        RunePoolMeta,
-        r#"
-        SELECT 
-            id, 
-            startTime as start_time, 
-            endTime as end_time, 
-            startUnits as start_units,
-            startCount as start_count, 
-            endUnits as end_units, 
-            endCount as end_count
-        FROM Rune_Pool_Data_Meta
-        "#
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Database error: {:?}", e);
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
-        )
-    })?;
-
-    Ok(Json(rows))
+    ApiQuery(window): ApiQuery<MetaFilter>,
+) -> Result<Json<RunePoolMeta>, AppError> {
+    let meta = History::runepool()
+        .window_meta(&pool, &window, None)
+        .await?;
+    Ok(Json(meta))
 }
 
+#[utoipa::path(
+    get,
+    path = "/runepooldata/intervals",
+    tag = "runepool",
+    params(TimeRangeFilter, RollupFilter),
+    responses(
+        (status = 200, description = "One page of intervals", body = Page<RunePoolIntervals>),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
 pub async fn query_intervals(
     State(pool): State<PgPool>,
-) -> Result<Json<Vec<RunePoolIntervals>>, (axum::http::StatusCode, String)> {
-    let rows = sqlx::query_as!(
-        RunePoolIntervals,
-        r#"
-        SELECT 
-            id,
-            startTime as start_time,
-            endTime as end_time,
-            count,
-            units
-        FROM rune_pool_data_intervals
-        "#
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Database error: {:?}", e);
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
-        )
-    })?;
-
-    Ok(Json(rows))
+    ApiQuery(range): ApiQuery<TimeRangeFilter>,
+    ApiQuery(rollup): ApiQuery<RollupFilter>,
+) -> Result<Json<Page<RunePoolIntervals>>, AppError> {
+    let window = range.window()?;
+    let source = match rollup.rollup()? {
+        None => r#"
+            SELECT 
+                id,
+                startTime as start_time,
+                endTime as end_time,
+                count,
+                units
+            FROM rune_pool_data_intervals
+            "#
+        .to_string(),
+        // count and units are running totals
+        Some(rollup) => format!(
+            "SELECT MIN(id) AS id, {}, {} AS count, {} AS units
+            FROM rune_pool_data_intervals
+            GROUP BY {}",
+            rollup.times("startTime"),
+            last("count", "startTime"),
+            last("units", "startTime"),
+            rollup.bucket("startTime")
+        ),
+    };
+    let query = format!(
+        "SELECT * FROM ({}) s WHERE {} {}",
+        source,
+        window.conditions("start_time", "end_time", "id"),
+        window.order_by("start_time", "id")
+    );
+    let rows = window
+        .bind(sqlx::query_as::<_, RunePoolIntervals>(&query))
+        .fetch_all(&pool)
+        .await?;
+
+    Ok(Json(window.page(rows, |row| (row.start_time, row.id))))
 }
-
-// async fn query_intervals(
-//     State(pool): State<PgPool>,
-//     Query(params): Query<IntervalQueryParams>,
-// ) -> Result<Json<Vec<RunePoolIntervals>>, (axum::http::StatusCode, String)> {
-//     // Check if `to` parameter is provided
-//     let to = params.to.ok_or((
-//         axum::http::StatusCode::BAD_REQUEST,
-//         "Missing 'to' timestamp".to_string(),
-//     ))?;
-
-//     // Determine the interval multiplier in seconds
-//     let multiplier = match params.interval.as_deref() {
-//         Some("hour") => 3600,
-//         Some("day") => 86400,
-//         Some("second") | None => 1, // Default to seconds if unspecified
-//         Some(_) => {
-//             return Err((
-//                 axum::http::StatusCode::BAD_REQUEST,
-//                 "Invalid interval value. Use 'second', 'hour', or 'day'.".to_string(),
-//             ));
-//         }
-//     };
-
-//     let count = params.count.unwrap_or(10); // Default to 10 rows if not provided
-
-//     // Calculate the start range based on 'to' and the interval
-//     let start_range = to - multiplier * (count as i64); // adjust based on interval
-
-//     // Query the database for the filtered data
-//     let rows = sqlx::query_as!(
-//         RunePoolIntervals,
-//         r#"
-//         SELECT
-//             id,
-//             startTime AS start_time,
-//             endTime AS end_time,
-//             count,
-//             units
-//         FROM rune_pool_data_intervals
-//         WHERE startTime >= $1 AND endTime <= $2
-//         LIMIT $3
-//         "#,
-//         start_range,  // Filter rows where the startTime is within the calculated range
-//         to,            // Filter rows where the endTime is within the provided 'to' timestamp
-//         count as i64   // Limit the number of rows returned
-//     )
-//     .fetch_all(&pool)
-//     .await
-//     .map_err(|e| {
-//         eprintln!("Database error: {:?}", e);
-//         (
-//             axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-//             format!("Database error: {}", e),
-//         )
-//     })?;
-
-//     Ok(Json(rows))
-// }
@@ -1,132 +1,394 @@
 // data_structs/depth_data.rs
-use axum::http::StatusCode;
 use axum::{extract::State, Json};
 use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize}; // query_data_from_db/rune_pool_data_query.rs
 use sqlx::PgPool;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+use crate::error::{AppError, ErrorBody};
+use crate::query_data_from_db::common::{ApiQuery, MetaFilter, Page, PoolFilter, TimeRangeFilter};
+use crate::query_data_from_db::midgard_history::History;
+use crate::query_data_from_db::rollup::{last, RollupFilter};
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct RunePoolDepthMeta {
-    pub id: i32,
+    pub pool: String,
     pub start_time: Option<i64>,
     pub end_time: Option<i64>,
-    pub price_shift_loss: Option<BigDecimal>, // Nullable field
-    pub luvi_increase: Option<BigDecimal>,    // Nullable field
-    pub start_asset_depth: Option<i64>,
-    pub start_rune_depth: Option<i64>,
-    pub start_lp_units: Option<i64>,
-    pub start_member_count: Option<i64>,
-    pub start_synth_units: Option<i64>,
-    pub end_asset_depth: Option<i64>,
-    pub end_rune_depth: Option<i64>,
-    pub end_lp_units: Option<i64>,
-    pub end_member_count: Option<i64>,
-    pub end_synth_units: Option<i64>,
+    #[schema(value_type = Option<String>)]
+    pub price_shift_loss: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub luvi_increase: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub start_asset_depth: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub start_rune_depth: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub start_lp_units: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub start_member_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub start_synth_units: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub end_asset_depth: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub end_rune_depth: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub end_lp_units: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub end_member_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub end_synth_units: Option<BigDecimal>,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct RunePoolDepthIntervals {
     pub id: i32,
+    pub pool: String,
     pub start_time: Option<i64>,
     pub end_time: Option<i64>,
-    pub asset_depth: Option<i64>,
-    pub rune_depth: Option<i64>,
-    pub asset_price: Option<BigDecimal>,     // Nullable field
-    pub asset_price_usd: Option<BigDecimal>, // Nullable field
-    pub liquidity_units: Option<i64>,
-    pub members_count: Option<i64>,
-    pub synth_units: Option<i64>,
-    pub synth_supply: Option<i64>,
-    pub units: Option<i64>,
-    pub luvi: Option<BigDecimal>, // Nullable field
+    #[schema(value_type = Option<String>)]
+    pub asset_depth: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub rune_depth: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub asset_price: Option<BigDecimal>, // unbounded, BTC.BTC prices run into the thousands
+    #[schema(value_type = Option<String>)]
+    pub asset_price_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub liquidity_units: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub members_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub synth_units: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub synth_supply: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub units: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    pub luvi: Option<BigDecimal>, // NULL when Midgard reported NaN
 }
 
+// Computed from the pool's stored intervals of the window
+#[utoipa::path(
+    get,
+    path = "/depthdata/meta",
+    tag = "depths",
+    params(PoolFilter, MetaFilter),
+    responses(
+        (status = 200, description = "Meta of the pool's window", body = RunePoolDepthMeta),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
 pub async fn query_meta(
     State(pool): State<PgPool>,
-) -> Result<Json<Vec<RunePoolDepthMeta>>, (StatusCode, String)> {
-    let rows = sqlx::query_as!(
-        RunePoolDepthMeta,
-        r#"
-        SELECT 
-            id, 
-            startTime as start_time, 
-            endTime as end_time, 
-            COALESCE(
-                NULLIF(priceShiftLoss::numeric::text, 'NaN')::numeric, 
-                NULL
-            ) as "price_shift_loss!: Option<BigDecimal>",
-            COALESCE(
-                NULLIF(luviIncrease::numeric::text, 'NaN')::numeric,
-                NULL
-            ) as "luvi_increase!: Option<BigDecimal>",
-            startAssetDepth as start_asset_depth,
-            startRuneDepth as start_rune_depth,
-            startLPUnits as start_lp_units,
-            startMemberCount as start_member_count,
-            startSynthUnits as start_synth_units,
-            endAssetDepth as end_asset_depth,
-            endRuneDepth as end_rune_depth,
-            endLPUnits as end_lp_units,
-            endMemberCount as end_member_count,
-            endSynthUnits as end_synth_units
-        FROM Rune_Pool_Depth_Meta
-        "#
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Database error: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
-        )
-    })?;
-
-    Ok(Json(rows))
+    ApiQuery(filter): ApiQuery<PoolFilter>,
+    ApiQuery(window): ApiQuery<MetaFilter>,
+) -> Result<Json<RunePoolDepthMeta>, AppError> {
+    let name = filter
+        .pool
+        .ok_or_else(|| AppError::Validation("pool is required".to_string()))?;
+    let meta = History::depths()
+        .window_meta(&pool, &window, Some(&name))
+        .await?;
+    Ok(Json(meta))
 }
 
+#[utoipa::path(
+    get,
+    path = "/depthdata/intervals",
+    tag = "depths",
+    params(PoolFilter, TimeRangeFilter, RollupFilter),
+    responses(
+        (status = 200, description = "One page of intervals", body = Page<RunePoolDepthIntervals>),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
 pub async fn query_intervals(
     State(pool): State<PgPool>,
-) -> Result<Json<Vec<RunePoolDepthIntervals>>, (StatusCode, String)> {
-    let rows = sqlx::query_as!(
-        RunePoolDepthIntervals,
-        r#"
-        SELECT 
-            id, 
-            startTime as start_time, 
-            endTime as end_time, 
-            assetDepth as asset_depth, 
-            runeDepth as rune_depth, 
-            COALESCE(
-                NULLIF(assetPrice::numeric::text, 'NaN')::numeric,
-                NULL
-            ) as "asset_price!: Option<BigDecimal>",
-            COALESCE(
-                NULLIF(assetPriceUSD::numeric::text, 'NaN')::numeric,
-                NULL
-            ) as "asset_price_usd!: Option<BigDecimal>",
-            liquidityUnits as liquidity_units,
-            membersCount as members_count,
-            synthUnits as synth_units,
-            synthSupply as synth_supply,
-            units as units,
-            COALESCE(
-                NULLIF(luvi::numeric::text, 'NaN')::numeric,
-                NULL
-            ) as "luvi!: Option<BigDecimal>"
-        FROM Rune_Pool_Depth_Intervals
-        "#
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Database error: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
+    ApiQuery(filter): ApiQuery<PoolFilter>,
+    ApiQuery(range): ApiQuery<TimeRangeFilter>,
+    ApiQuery(rollup): ApiQuery<RollupFilter>,
+) -> Result<Json<Page<RunePoolDepthIntervals>>, AppError> {
+    let window = range.window()?;
+    let source = match rollup.rollup()? {
+        None => r#"
+            SELECT 
+                id, 
+                pool,
+                startTime as start_time, 
+                endTime as end_time, 
+                assetDepth as asset_depth, 
+                runeDepth as rune_depth, 
+                NULLIF(assetPrice, 'NaN') as asset_price,
+                NULLIF(assetPriceUSD, 'NaN') as asset_price_usd,
+                liquidityUnits as liquidity_units,
+                membersCount as members_count,
+                synthUnits as synth_units,
+                synthSupply as synth_supply,
+                units as units,
+                NULLIF(luvi, 'NaN') as luvi
+            FROM Rune_Pool_Depth_Intervals
+            WHERE ($6::text IS NULL OR pool = $6)
+            "#
+        .to_string(),
+        // every depth column is a snapshot at the end of its hour
+        Some(rollup) => {
+            let columns: Vec<String> = [
+                ("assetDepth", "asset_depth"),
+                ("runeDepth", "rune_depth"),
+                ("NULLIF(assetPrice, 'NaN')", "asset_price"),
+                ("NULLIF(assetPriceUSD, 'NaN')", "asset_price_usd"),
+                ("liquidityUnits", "liquidity_units"),
+                ("membersCount", "members_count"),
+                ("synthUnits", "synth_units"),
+                ("synthSupply", "synth_supply"),
+                ("units", "units"),
+                ("NULLIF(luvi, 'NaN')", "luvi"),
+            ]
+            .iter()
+            .map(|(column, name)| format!("{} AS {}", last(column, "startTime"), name))
+            .collect();
+            format!(
+                "SELECT MIN(id) AS id, pool, {}, {}
+                FROM Rune_Pool_Depth_Intervals
+                WHERE ($6::text IS NULL OR pool = $6)
+                GROUP BY pool, {}",
+                rollup.times("startTime"),
+                columns.join(", "),
+                rollup.bucket("startTime")
+            )
+        }
+    };
+    let query = format!(
+        "SELECT * FROM ({}) s WHERE {} {}",
+        source,
+        window.conditions("start_time", "end_time", "id"),
+        window.order_by("start_time", "id")
+    );
+    let rows = window
+        .bind(sqlx::query_as::<_, RunePoolDepthIntervals>(&query))
+        .bind(filter.pool)
+        .fetch_all(&pool)
+        .await?;
+
+    Ok(Json(window.page(rows, |row| (row.start_time, row.id))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structs::depth_data::RootDepthDetails;
+    use crate::fetch_data_from_midgard::midgard_client::Interval;
+    use crate::insert_data_post_migration::depth_data_insert_script;
+    use crate::query_data_from_db::common::Order;
+    use crate::query_data_from_db::midgard_history::{self, HistoryQuery};
+    use axum::extract::Path;
+    use std::str::FromStr;
+
+    // three hours of BTC.BTC depths as recorded with `fixture_mode = "record"`
+    const BTC_DEPTHS: &str =
+        include_str!("../../fixtures/depths_BTC.BTC_hour_to1714532400_count3.json");
+    // the same three hours and the one before them
+    const BTC_DEPTHS_FROM_HOUR_BEFORE: &str =
+        include_str!("../../fixtures/depths_BTC.BTC_hour_to1714532400_count4.json");
+
+    fn all() -> ApiQuery<TimeRangeFilter> {
+        ApiQuery(TimeRangeFilter::default())
+    }
+
+    fn as_stored() -> ApiQuery<RollupFilter> {
+        ApiQuery(RollupFilter { interval: None })
+    }
+
+    fn by_pool(pool: &str) -> ApiQuery<PoolFilter> {
+        ApiQuery(PoolFilter {
+            pool: Some(pool.to_string()),
+        })
+    }
+
+    // Regression for the old NUMERIC(10, 8) columns, which rejected any price >= 100
+    #[sqlx::test(migrations = "./migrations")]
+    async fn stores_and_returns_high_asset_prices(pool: PgPool) {
+        let data: RootDepthDetails = serde_json::from_str(BTC_DEPTHS).unwrap();
+        depth_data_insert_script::insert_data(&pool, "BTC.BTC", data, None)
+            .await
+            .unwrap();
+
+        let Json(page) =
+            query_intervals(State(pool.clone()), by_pool("BTC.BTC"), all(), as_stored())
+                .await
+                .unwrap();
+        let intervals = page.data;
+
+        let prices: Vec<_> = intervals
+            .iter()
+            .map(|i| i.asset_price.clone().unwrap())
+            .collect();
+        let expected: Vec<_> = ["9110.6594548219", "11195.8474663084", "9493.6415699240"]
+            .iter()
+            .map(|p| BigDecimal::from_str(p).unwrap())
+            .collect();
+        assert_eq!(prices, expected);
+        assert_eq!(
+            intervals[2].asset_price_usd,
+            Some(BigDecimal::from_str("51052.0575422664").unwrap())
+        );
+
+        // meta is computed from those intervals: its depths are Midgard's own
+        let upstream: RootDepthDetails = serde_json::from_str(BTC_DEPTHS).unwrap();
+        let Json(meta) = query_meta(
+            State(pool),
+            by_pool("BTC.BTC"),
+            ApiQuery(MetaFilter::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(meta.start_time, Some(upstream.meta.startTime as i64));
+        assert_eq!(
+            meta.start_asset_depth,
+            Some(BigDecimal::from(upstream.meta.startAssetDepth))
+        );
+        assert_eq!(
+            meta.end_lp_units,
+            Some(BigDecimal::from(upstream.meta.endLPUnits))
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn measures_price_shift_from_the_interval_before_the_window(pool: PgPool) {
+        let data: RootDepthDetails = serde_json::from_str(BTC_DEPTHS_FROM_HOUR_BEFORE).unwrap();
+        depth_data_insert_script::insert_data(&pool, "BTC.BTC", data, None)
+            .await
+            .unwrap();
+
+        // Midgard's meta for the last three of those hours
+        let upstream: RootDepthDetails = serde_json::from_str(BTC_DEPTHS).unwrap();
+        let Json(meta) = query_meta(
+            State(pool),
+            by_pool("BTC.BTC"),
+            ApiQuery(MetaFilter {
+                from: Some(upstream.meta.startTime as i64),
+                to: Some(upstream.meta.endTime as i64),
+            }),
         )
-    })?;
+        .await
+        .unwrap();
+        assert_eq!(meta.price_shift_loss, upstream.meta.priceShiftLoss);
+        assert_eq!(meta.luvi_increase, upstream.meta.luviIncrease);
+    }
 
-    std::println!("The query has been completed successfully! depth data intervals");
-    Ok(Json(rows))
+    #[sqlx::test(migrations = "./migrations")]
+    async fn keeps_every_digit_and_stores_nan_as_null(pool: PgPool) {
+        // more digits than an f64 holds, and a luvi Midgard could not compute
+        let mut json: serde_json::Value = serde_json::from_str(BTC_DEPTHS).unwrap();
+        json["intervals"][0]["assetPrice"] = "9110.659454821998765432109".into();
+        json["intervals"][0]["luvi"] = "NaN".into();
+        json["meta"]["luviIncrease"] = "NaN".into();
+        json["meta"]["priceShiftLoss"] = "0.999979180312345678901".into();
+        let data: RootDepthDetails = serde_json::from_str(&json.to_string()).unwrap();
+        depth_data_insert_script::insert_data(&pool, "BTC.BTC", data, None)
+            .await
+            .unwrap();
+
+        let (price, luvi): (BigDecimal, Option<BigDecimal>) = sqlx::query_as(
+            "SELECT assetPrice, luvi FROM Rune_Pool_Depth_Intervals ORDER BY startTime LIMIT 1",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            price,
+            BigDecimal::from_str("9110.659454821998765432109").unwrap()
+        );
+        assert_eq!(luvi, None);
+        let (loss, increase): (BigDecimal, Option<BigDecimal>) =
+            sqlx::query_as("SELECT priceShiftLoss, luviIncrease FROM Rune_Pool_Depth_Meta")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            loss,
+            BigDecimal::from_str("0.999979180312345678901").unwrap()
+        );
+        assert_eq!(increase, None);
+
+        // and Midgard's own route writes them back out the same way
+        let Json(history) = midgard_history::depths(
+            State(pool),
+            Path("BTC.BTC".to_string()),
+            ApiQuery(HistoryQuery {
+                interval: Some(Interval::Hour),
+                ..HistoryQuery::default()
+            }),
+        )
+        .await
+        .unwrap();
+        let first = &history["intervals"][0];
+        assert_eq!(first["assetPrice"], "9110.659454821998765432109");
+        assert_eq!(first["luvi"], "NaN");
+        assert_eq!(
+            first["assetPriceUSD"],
+            json["intervals"][0]["assetPriceUSD"]
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn filters_intervals_by_pool(pool: PgPool) {
+        for pool_name in ["BTC.BTC", "ETH.ETH"] {
+            let data: RootDepthDetails = serde_json::from_str(BTC_DEPTHS).unwrap();
+            depth_data_insert_script::insert_data(&pool, pool_name, data, None)
+                .await
+                .unwrap();
+        }
+
+        let Json(eth) =
+            query_intervals(State(pool.clone()), by_pool("ETH.ETH"), all(), as_stored())
+                .await
+                .unwrap();
+        assert_eq!(eth.data.len(), 3);
+        assert!(eth.data.iter().all(|i| i.pool == "ETH.ETH"));
+
+        // both pools share start times, the id breaks the tie across pages
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let range = TimeRangeFilter {
+                count: Some(4),
+                cursor,
+                order: Order::Desc,
+                ..TimeRangeFilter::default()
+            };
+            let Json(page) = query_intervals(
+                State(pool.clone()),
+                ApiQuery(PoolFilter { pool: None }),
+                ApiQuery(range),
+                as_stored(),
+            )
+            .await
+            .unwrap();
+            seen.extend(page.data.iter().map(|i| (i.start_time, i.id)));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen.len(), 6);
+        assert!(seen.windows(2).all(|pair| pair[0] > pair[1]));
+
+        let range = TimeRangeFilter {
+            from: Some(1_714_525_200),
+            ..TimeRangeFilter::default()
+        };
+        let Json(later) = query_intervals(
+            State(pool),
+            by_pool("BTC.BTC"),
+            ApiQuery(range),
+            as_stored(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(later.data.len(), 2);
+        assert_eq!(later.next_cursor, None);
+    }
 }
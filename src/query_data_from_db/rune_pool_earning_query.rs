@@ -1,21 +1,29 @@
-use axum::{
-    extract::{Query, State},
-    Json,
-    http::StatusCode,
-};
-use serde::{Serialize, Deserialize};
-use sqlx::{FromRow, PgPool, Row, postgres::PgRow};
+use crate::error::{AppError, ErrorBody};
+use crate::query_data_from_db::common::{ApiQuery, MetaFilter, Page, TimeRangeFilter};
+use crate::query_data_from_db::midgard_history::History;
+use crate::query_data_from_db::rollup::{last, RollupFilter};
+use axum::{extract::State, Json};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, FromRow, PgPool, Row};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct EarningDataPoolData {
     id: i32,
     pool: String,
-    asset_liquidity_fees: Option<i64>,
-    rune_liquidity_fees: Option<i64>,
-    total_liquidity_fees_rune: Option<i64>,
-    saver_earning: Option<i64>,
-    rewards: Option<i64>,
-    earnings: Option<i64>,
+    #[schema(value_type = Option<String>)]
+    asset_liquidity_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    rune_liquidity_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_liquidity_fees_rune: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    saver_earning: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    rewards: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    earnings: Option<BigDecimal>,
 }
 
 impl FromRow<'_, PgRow> for EarningDataPoolData {
@@ -33,16 +41,20 @@ impl FromRow<'_, PgRow> for EarningDataPoolData {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct EarningDataRunePoolMeta {
-    id: i32,
     start_time: Option<i64>,
     end_time: Option<i64>,
-    liquidity_fees: Option<i64>,
-    block_rewards: Option<i64>,
-    earnings: Option<i64>,
-    bonding_earnings: Option<i64>,
-    liquidity_earnings: Option<i64>,
+    #[schema(value_type = Option<String>)]
+    liquidity_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    block_rewards: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    earnings: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    bonding_earnings: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    liquidity_earnings: Option<BigDecimal>,
     avg_node_count: Option<f64>,
     rune_price_usd: Option<f64>,
 }
@@ -50,7 +62,6 @@ pub struct EarningDataRunePoolMeta {
 impl FromRow<'_, PgRow> for EarningDataRunePoolMeta {
     fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
         Ok(EarningDataRunePoolMeta {
-            id: row.try_get("id")?,
             start_time: row.try_get("start_time")?,
             end_time: row.try_get("end_time")?,
             liquidity_fees: row.try_get("liquidity_fees")?,
@@ -64,16 +75,21 @@ impl FromRow<'_, PgRow> for EarningDataRunePoolMeta {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct EarningDataRunePoolInterval {
     id: i32,
     start_time: Option<i64>,
     end_time: Option<i64>,
-    liquidity_fees: Option<i64>,
-    block_rewards: Option<i64>,
-    earnings: Option<i64>,
-    bonding_earnings: Option<i64>,
-    liquidity_earnings: Option<i64>,
+    #[schema(value_type = Option<String>)]
+    liquidity_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    block_rewards: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    earnings: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    bonding_earnings: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    liquidity_earnings: Option<BigDecimal>,
     avg_node_count: Option<f64>,
     rune_price_usd: Option<f64>,
     pools: Vec<EarningDataPoolData>,
@@ -81,8 +97,9 @@ pub struct EarningDataRunePoolInterval {
 
 impl FromRow<'_, PgRow> for EarningDataRunePoolInterval {
     fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
-        let pools: Vec<EarningDataPoolData> = serde_json::from_value(row.try_get("pools")?).unwrap_or_default();
-        
+        let pools: Vec<EarningDataPoolData> =
+            serde_json::from_value(row.try_get("pools")?).unwrap_or_default();
+
         Ok(EarningDataRunePoolInterval {
             id: row.try_get("id")?,
             start_time: row.try_get("start_time")?,
@@ -99,157 +116,207 @@ impl FromRow<'_, PgRow> for EarningDataRunePoolInterval {
     }
 }
 
-#[derive(Deserialize)]
-pub struct TimeFilter {
-    start_time: Option<i64>,
-    end_time: Option<i64>,
-}
-
-pub async fn fetch_pool_data(
-    State(pool): State<PgPool>,
-) -> Result<Json<Vec<EarningDataPoolData>>, (StatusCode, String)> {
-    let rows = sqlx::query_as::<_, EarningDataPoolData>(
-        "SELECT * FROM earning_data_pool_data"
+// Computed from the stored intervals of the window
+#[utoipa::path(
+    get,
+    path = "/earningData/meta",
+    tag = "earnings",
+    params(MetaFilter),
+    responses(
+        (status = 200, description = "Meta of the window", body = EarningDataRunePoolMeta),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
     )
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Error fetching pool data: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
-        )
-    })?;
-
-    Ok(Json(rows))
+)]
+pub async fn fetch_meta(
+    State(pool): State<PgPool>,
+    ApiQuery(window): ApiQuery<MetaFilter>,
+) -> Result<Json<EarningDataRunePoolMeta>, AppError> {
+    let meta = History::earnings()
+        .window_meta(&pool, &window, None)
+        .await?;
+    Ok(Json(meta))
 }
 
-pub async fn fetch_meta(
+#[utoipa::path(
+    get,
+    path = "/earningData/intervals",
+    tag = "earnings",
+    params(TimeRangeFilter, RollupFilter),
+    responses(
+        (status = 200, description = "One page of intervals", body = Page<EarningDataRunePoolInterval>),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
+pub async fn fetch_intervals(
     State(pool): State<PgPool>,
-    Query(filter): Query<TimeFilter>,
-) -> Result<Json<Vec<EarningDataRunePoolMeta>>, (StatusCode, String)> {
-    let query = match (filter.start_time, filter.end_time) {
-        (Some(start), Some(end)) => {
-            sqlx::query_as::<_, EarningDataRunePoolMeta>(
-                "SELECT * FROM earning_data_rune_pool_meta WHERE start_time >= $1 AND end_time <= $2"
-            )
-            .bind(start)
-            .bind(end)
-        },
-        (Some(start), None) => {
-            sqlx::query_as::<_, EarningDataRunePoolMeta>(
-                "SELECT * FROM earning_data_rune_pool_meta WHERE start_time >= $1"
-            )
-            .bind(start)
-        },
-        (None, Some(end)) => {
-            sqlx::query_as::<_, EarningDataRunePoolMeta>(
-                "SELECT * FROM earning_data_rune_pool_meta WHERE end_time <= $1"
-            )
-            .bind(end)
-        },
-        (None, None) => {
-            sqlx::query_as::<_, EarningDataRunePoolMeta>(
-                "SELECT * FROM earning_data_rune_pool_meta"
-            )
-        }
+    ApiQuery(range): ApiQuery<TimeRangeFilter>,
+    ApiQuery(rollup): ApiQuery<RollupFilter>,
+) -> Result<Json<Page<EarningDataRunePoolInterval>>, AppError> {
+    let window = range.window()?;
+    let source = match rollup.rollup()? {
+        None => r#"
+            SELECT 
+                i.id,
+                i.start_time,
+                i.end_time,
+                i.liquidity_fees,
+                i.block_rewards,
+                i.earnings,
+                i.bonding_earnings,
+                i.liquidity_earnings,
+                i.avg_node_count,
+                i.rune_price_usd,
+                COALESCE(
+                    json_agg(
+                        json_build_object(
+                            'id', p.id,
+                            'pool', p.pool,
+                            'asset_liquidity_fees', p.asset_liquidity_fees::text,
+                            'rune_liquidity_fees', p.rune_liquidity_fees::text,
+                            'total_liquidity_fees_rune', p.total_liquidity_fees_rune::text,
+                            'saver_earning', p.saver_earning::text,
+                            'rewards', p.rewards::text,
+                            'earnings', p.earnings::text
+                        )
+                    ) FILTER (WHERE p.id IS NOT NULL),
+                    '[]'::json
+                ) as pools
+            FROM earning_data_rune_pool_interval i
+            LEFT JOIN earning_data_pool_data p ON i.id = p.interval_id
+            GROUP BY i.id
+            "#
+        .to_string(),
+        // earnings add up, per pool too; the node count is averaged over the
+        // hours and the price is the one at the end of the bucket
+        Some(rollup) => format!(
+            r#"
+            SELECT
+                MIN(i.id) AS id,
+                {},
+                SUM(i.liquidity_fees) AS liquidity_fees,
+                SUM(i.block_rewards) AS block_rewards,
+                SUM(i.earnings) AS earnings,
+                SUM(i.bonding_earnings) AS bonding_earnings,
+                SUM(i.liquidity_earnings) AS liquidity_earnings,
+                AVG(i.avg_node_count) AS avg_node_count,
+                {} AS rune_price_usd,
+                COALESCE((
+                    SELECT json_agg(pool_totals ORDER BY pool_totals->>'pool')
+                    FROM (
+                        SELECT json_build_object(
+                            'id', MIN(p.id),
+                            'pool', p.pool,
+                            'asset_liquidity_fees', SUM(p.asset_liquidity_fees)::text,
+                            'rune_liquidity_fees', SUM(p.rune_liquidity_fees)::text,
+                            'total_liquidity_fees_rune', SUM(p.total_liquidity_fees_rune)::text,
+                            'saver_earning', SUM(p.saver_earning)::text,
+                            'rewards', SUM(p.rewards)::text,
+                            'earnings', SUM(p.earnings)::text
+                        ) AS pool_totals
+                        FROM earning_data_pool_data p
+                        WHERE p.interval_id = ANY(array_agg(i.id))
+                        GROUP BY p.pool
+                    ) pools
+                ), '[]'::json) AS pools
+            FROM earning_data_rune_pool_interval i
+            GROUP BY {}
+            "#,
+            rollup.times("i.start_time"),
+            last("i.rune_price_usd", "i.start_time"),
+            rollup.bucket("i.start_time")
+        ),
     };
-
-    let rows = query
+    let query = format!(
+        "SELECT * FROM ({}) s WHERE {} {}",
+        source,
+        window.conditions("start_time", "end_time", "id"),
+        window.order_by("start_time", "id")
+    );
+    let rows = window
+        .bind(sqlx::query_as::<_, EarningDataRunePoolInterval>(&query))
         .fetch_all(&pool)
-        .await
-        .map_err(|e| {
-            eprintln!("Error fetching meta: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            )
-        })?;
+        .await?;
 
-    Ok(Json(rows))
+    Ok(Json(window.page(rows, |row| (row.start_time, row.id))))
 }
 
-pub async fn fetch_intervals(
-    State(pool): State<PgPool>,
-    Query(filter): Query<TimeFilter>,
-) -> Result<Json<Vec<EarningDataRunePoolInterval>>, (StatusCode, String)> {
-    let base_query = r#"
-        SELECT 
-            i.id,
-            i.start_time,
-            i.end_time,
-            i.liquidity_fees,
-            i.block_rewards,
-            i.earnings,
-            i.bonding_earnings,
-            i.liquidity_earnings,
-            i.avg_node_count,
-            i.rune_price_usd,
-            COALESCE(
-                json_agg(
-                    json_build_object(
-                        'id', p.id,
-                        'pool', p.pool,
-                        'asset_liquidity_fees', p.asset_liquidity_fees,
-                        'rune_liquidity_fees', p.rune_liquidity_fees,
-                        'total_liquidity_fees_rune', p.total_liquidity_fees_rune,
-                        'saver_earning', p.saver_earning,
-                        'rewards', p.rewards,
-                        'earnings', p.earnings
-                    )
-                ) FILTER (WHERE p.id IS NOT NULL),
-                '[]'::json
-            ) as pools
-        FROM earning_data_rune_pool_interval i
-        LEFT JOIN earning_data_pool_data p ON i.id = p.interval_id
-    "#;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structs::earning_history::RunePoolInterval;
+    use crate::fetch_data_from_midgard::midgard_client::Interval;
+    use crate::insert_data_post_migration::earning_data_insert_script;
 
-    let query = match (filter.start_time, filter.end_time) {
-        (Some(start), Some(end)) => {
-            format!(
-                "{} WHERE i.start_time >= $1 AND i.start_time <= $2 GROUP BY i.id ORDER BY i.start_time DESC",
-                base_query
-            )
-        },
-        (Some(start), None) => {
-            format!(
-                "{} WHERE i.start_time >= $1 GROUP BY i.id ORDER BY i.start_time DESC",
-                base_query
-            )
-        },
-        (None, Some(end)) => {
-            format!(
-                "{} WHERE i.start_time <= $1 GROUP BY i.id ORDER BY i.start_time DESC",
-                base_query
-            )
-        },
-        (None, None) => {
-            format!(
-                "{} GROUP BY i.id ORDER BY i.start_time DESC",
-                base_query
-            )
+    #[sqlx::test(migrations = "./migrations")]
+    async fn rolls_earnings_and_pools_up_into_days(db: PgPool) {
+        // two hours of 2024-05-01 UTC; ETH.ETH earns in the second one only
+        let day = 1_714_521_600;
+        let pool = |name: &str, fees: u32, earnings: u32| {
+            serde_json::json!({
+                "pool": name,
+                "assetLiquidityFees": fees.to_string(), "runeLiquidityFees": "0",
+                "totalLiquidityFeesRune": fees.to_string(), "saverEarning": "0",
+                "rewards": "0", "earnings": earnings.to_string(),
+            })
+        };
+        let hours = [
+            (100, 40.0, 2.0, vec![pool("BTC.BTC", 100, 300)]),
+            (
+                250,
+                44.0,
+                3.0,
+                vec![pool("BTC.BTC", 50, 150), pool("ETH.ETH", 200, 600)],
+            ),
+        ];
+        for (hour, (fees, nodes, price, pools)) in hours.into_iter().enumerate() {
+            let start = day + hour as i64 * 3_600;
+            let row = serde_json::json!({
+                "startTime": start.to_string(), "endTime": (start + 3_600).to_string(),
+                "liquidityFees": fees.to_string(), "blockRewards": "10",
+                "earnings": (fees + 10).to_string(), "bondingEarnings": "5",
+                "liquidityEarnings": (fees + 5).to_string(),
+                "avgNodeCount": nodes.to_string(), "runePriceUSD": price.to_string(),
+                "pools": pools,
+            });
+            let interval: RunePoolInterval = serde_json::from_str(&row.to_string()).unwrap();
+            earning_data_insert_script::insert_rune_pool_intervals(&[interval], None, &db)
+                .await
+                .unwrap();
         }
-    };
-
-    let query = sqlx::query_as::<_, EarningDataRunePoolInterval>(&query);
 
-    let query = match (filter.start_time, filter.end_time) {
-        (Some(start), Some(end)) => query.bind(start).bind(end),
-        (Some(start), None) => query.bind(start),
-        (None, Some(end)) => query.bind(end),
-        (None, None) => query,
-    };
-
-    let rows = query
-        .fetch_all(&pool)
+        let rollup = RollupFilter {
+            interval: Some(Interval::Day),
+        };
+        let Json(page) = fetch_intervals(
+            State(db),
+            ApiQuery(TimeRangeFilter::default()),
+            ApiQuery(rollup),
+        )
         .await
-        .map_err(|e| {
-            eprintln!("Error fetching intervals: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            )
-        })?;
+        .unwrap();
+        let days = serde_json::to_value(&page.data).unwrap();
+
+        assert_eq!(days.as_array().unwrap().len(), 1);
+        assert_eq!(days[0]["start_time"], day);
+        assert_eq!(days[0]["end_time"], day + 86_400);
+        assert_eq!(days[0]["liquidity_fees"], "350");
+        assert_eq!(days[0]["block_rewards"], "20");
+        assert_eq!(days[0]["earnings"], "370");
+        assert_eq!(days[0]["bonding_earnings"], "10");
+        assert_eq!(days[0]["liquidity_earnings"], "360");
+        assert_eq!(days[0]["avg_node_count"], 42.0);
+        // the price at the end of the bucket
+        assert_eq!(days[0]["rune_price_usd"], 3.0);
 
-    Ok(Json(rows))
+        // one entry per pool, summed over the hours it shows up in
+        let pools = days[0]["pools"].as_array().unwrap();
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[0]["pool"], "BTC.BTC");
+        assert_eq!(pools[0]["asset_liquidity_fees"], "150");
+        assert_eq!(pools[0]["total_liquidity_fees_rune"], "150");
+        assert_eq!(pools[0]["earnings"], "450");
+        assert_eq!(pools[0]["saver_earning"], "0");
+        assert_eq!(pools[1]["pool"], "ETH.ETH");
+        assert_eq!(pools[1]["earnings"], "600");
+    }
 }
@@ -1,40 +1,75 @@
-use axum::{Json, extract::State, http::StatusCode};
-use serde::{Serialize, Deserialize};
-use sqlx::{FromRow, PgPool, Row, postgres::PgRow};
+use crate::error::{AppError, ErrorBody};
+use crate::insert_data_post_migration::swap_data_insert_script::AGGREGATE_POOL;
+use crate::query_data_from_db::common::{ApiQuery, MetaFilter, Page, PoolFilter, TimeRangeFilter};
+use crate::query_data_from_db::midgard_history::{swap_aggregates, History, SWAP_KINDS};
+use crate::query_data_from_db::rollup::RollupFilter;
+use axum::{extract::State, Json};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, FromRow, PgPool, Row};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct SwapDataRunePoolMeta {
-    id: i32,
+    pool: String,
     start_time: Option<i64>,
     end_time: Option<i64>,
-    to_asset_count: Option<i64>,
-    to_rune_count: Option<i64>,
-    to_trade_count: Option<i64>,
-    from_trade_count: Option<i64>,
-    synth_mint_count: Option<i64>,
-    synth_redeem_count: Option<i64>,
-    total_count: Option<i64>,
-    to_asset_volume: Option<i64>,
-    to_rune_volume: Option<i64>,
-    to_trade_volume: Option<i64>,
-    from_trade_volume: Option<i64>,
-    synth_mint_volume: Option<i64>,
-    synth_redeem_volume: Option<i64>,
-    total_volume: Option<i64>,
-    to_asset_volume_usd: Option<i64>,    // changed from f64 to i64
-    to_rune_volume_usd: Option<i64>,     // changed from f64 to i64
-    to_trade_volume_usd: Option<i64>,    // changed from f64 to i64
-    from_trade_volume_usd: Option<i64>,  // changed from f64 to i64
-    synth_mint_volume_usd: Option<i64>,  // changed from f64 to i64
-    synth_redeem_volume_usd: Option<i64>,// changed from f64 to i64
-    total_volume_usd: Option<i64>,       // changed from f64 to i64
-    to_asset_fees: Option<i64>,
-    to_rune_fees: Option<i64>,
-    to_trade_fees: Option<i64>,
-    from_trade_fees: Option<i64>,
-    synth_mint_fees: Option<i64>,
-    synth_redeem_fees: Option<i64>,
-    total_fees: Option<i64>,
+    #[schema(value_type = Option<String>)]
+    to_asset_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_rune_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_trade_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    from_trade_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_mint_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_redeem_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_asset_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_rune_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_trade_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    from_trade_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_mint_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_redeem_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_asset_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_rune_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_trade_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    from_trade_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_mint_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_redeem_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_asset_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_rune_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_trade_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    from_trade_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_mint_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_redeem_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_fees: Option<BigDecimal>,
     to_asset_average_slip: Option<f64>,
     to_rune_average_slip: Option<f64>,
     to_trade_average_slip: Option<f64>,
@@ -48,7 +83,7 @@ pub struct SwapDataRunePoolMeta {
 impl FromRow<'_, PgRow> for SwapDataRunePoolMeta {
     fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
         Ok(SwapDataRunePoolMeta {
-            id: row.try_get("id")?,
+            pool: row.try_get("pool")?,
             start_time: row.try_get("start_time")?,
             end_time: row.try_get("end_time")?,
             to_asset_count: row.try_get("to_asset_count")?,
@@ -91,46 +126,98 @@ impl FromRow<'_, PgRow> for SwapDataRunePoolMeta {
     }
 }
 
+// Computed from the stored intervals of the window; without `?pool=` the
+// all-pools series
+#[utoipa::path(
+    get,
+    path = "/swapData/meta",
+    tag = "swaps",
+    params(PoolFilter, MetaFilter),
+    responses(
+        (status = 200, description = "Meta of the window", body = SwapDataRunePoolMeta),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
 pub async fn fetch_meta(
     State(pool): State<PgPool>,
-) -> Result<Json<Vec<SwapDataRunePoolMeta>>, (StatusCode, String)> {
-    let rows = sqlx::query_as::<_, SwapDataRunePoolMeta>(
-        "SELECT * FROM swap_data_rune_pool_meta"
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Error fetching meta: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
-        )
-    })?;
-
-    Ok(Json(rows))
+    ApiQuery(filter): ApiQuery<PoolFilter>,
+    ApiQuery(window): ApiQuery<MetaFilter>,
+) -> Result<Json<SwapDataRunePoolMeta>, AppError> {
+    let name = filter.pool.unwrap_or_else(|| AGGREGATE_POOL.to_string());
+    let meta = History::swaps()
+        .window_meta(&pool, &window, Some(&name))
+        .await?;
+    Ok(Json(meta))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct SwapDataRunePoolInterval {
     id: i32,
+    pool: String,
     start_time: Option<i64>,
     end_time: Option<i64>,
-    to_asset_count: Option<i64>,
-    to_rune_count: Option<i64>,
-    to_trade_count: Option<i64>,
-    from_trade_count: Option<i64>,
-    synth_mint_count: Option<i64>,
-    synth_redeem_count: Option<i64>,
-    total_count: Option<i64>,
-    to_asset_volume: Option<i64>,
-    to_rune_volume: Option<i64>,
-    to_trade_volume: Option<i64>,
-    from_trade_volume: Option<i64>,
-    synth_mint_volume: Option<i64>,
-    synth_redeem_volume: Option<i64>,
-    total_volume: Option<i64>,
+    #[schema(value_type = Option<String>)]
+    to_asset_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_rune_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_trade_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    from_trade_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_mint_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_redeem_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_count: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_asset_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_rune_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_trade_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    from_trade_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_mint_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_redeem_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_volume: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_asset_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_rune_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_trade_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    from_trade_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_mint_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_redeem_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_volume_usd: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_asset_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_rune_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    to_trade_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    from_trade_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_mint_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    synth_redeem_fees: Option<BigDecimal>,
+    #[schema(value_type = Option<String>)]
+    total_fees: Option<BigDecimal>,
     to_asset_average_slip: Option<f64>,
     to_rune_average_slip: Option<f64>,
+    to_trade_average_slip: Option<f64>,
+    from_trade_average_slip: Option<f64>,
+    synth_mint_average_slip: Option<f64>,
+    synth_redeem_average_slip: Option<f64>,
     average_slip: Option<f64>,
     rune_price_usd: Option<f64>,
 }
@@ -139,6 +226,7 @@ impl FromRow<'_, PgRow> for SwapDataRunePoolInterval {
     fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
         Ok(SwapDataRunePoolInterval {
             id: row.try_get("id")?,
+            pool: row.try_get("pool")?,
             start_time: row.try_get("start_time")?,
             end_time: row.try_get("end_time")?,
             to_asset_count: row.try_get("to_asset_count")?,
@@ -155,29 +243,237 @@ impl FromRow<'_, PgRow> for SwapDataRunePoolInterval {
             synth_mint_volume: row.try_get("synth_mint_volume")?,
             synth_redeem_volume: row.try_get("synth_redeem_volume")?,
             total_volume: row.try_get("total_volume")?,
+            to_asset_volume_usd: row.try_get("to_asset_volume_usd")?,
+            to_rune_volume_usd: row.try_get("to_rune_volume_usd")?,
+            to_trade_volume_usd: row.try_get("to_trade_volume_usd")?,
+            from_trade_volume_usd: row.try_get("from_trade_volume_usd")?,
+            synth_mint_volume_usd: row.try_get("synth_mint_volume_usd")?,
+            synth_redeem_volume_usd: row.try_get("synth_redeem_volume_usd")?,
+            total_volume_usd: row.try_get("total_volume_usd")?,
+            to_asset_fees: row.try_get("to_asset_fees")?,
+            to_rune_fees: row.try_get("to_rune_fees")?,
+            to_trade_fees: row.try_get("to_trade_fees")?,
+            from_trade_fees: row.try_get("from_trade_fees")?,
+            synth_mint_fees: row.try_get("synth_mint_fees")?,
+            synth_redeem_fees: row.try_get("synth_redeem_fees")?,
+            total_fees: row.try_get("total_fees")?,
             to_asset_average_slip: row.try_get("to_asset_average_slip")?,
             to_rune_average_slip: row.try_get("to_rune_average_slip")?,
+            to_trade_average_slip: row.try_get("to_trade_average_slip")?,
+            from_trade_average_slip: row.try_get("from_trade_average_slip")?,
+            synth_mint_average_slip: row.try_get("synth_mint_average_slip")?,
+            synth_redeem_average_slip: row.try_get("synth_redeem_average_slip")?,
             average_slip: row.try_get("average_slip")?,
             rune_price_usd: row.try_get("rune_price_usd")?,
         })
     }
 }
 
+// `?sort_by=total_volume` ranks the window's intervals by one of their
+// counts, volumes or fees instead of returning them in time; with
+// `order=desc` the largest come first
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SortFilter {
+    #[param(schema_with = crate::openapi::swap_sort_keys)]
+    pub sort_by: Option<String>,
+}
+
+// The interval columns `sort_by` accepts
+pub fn sort_keys() -> Vec<String> {
+    let mut keys = Vec::new();
+    for (_, kind) in SWAP_KINDS.iter().chain(&[("total", "total")]) {
+        for suffix in ["count", "volume", "volume_usd", "fees"] {
+            keys.push(format!("{}_{}", kind, suffix));
+        }
+    }
+    keys
+}
+
+// Without `?pool=` the all-pools series is returned
+#[utoipa::path(
+    get,
+    path = "/swapData/intervals",
+    tag = "swaps",
+    params(PoolFilter, TimeRangeFilter, RollupFilter, SortFilter),
+    responses(
+        (status = 200, description = "One page of intervals", body = Page<SwapDataRunePoolInterval>),
+        (status = 400, description = "Invalid query parameters", body = ErrorBody),
+    )
+)]
 pub async fn fetch_intervals(
     State(pool): State<PgPool>,
-) -> Result<Json<Vec<SwapDataRunePoolInterval>>, (StatusCode, String)> {
-    let rows = sqlx::query_as::<_, SwapDataRunePoolInterval>(
-        "SELECT * FROM swap_data_rune_pool_interval"
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Error fetching intervals: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
+    ApiQuery(filter): ApiQuery<PoolFilter>,
+    ApiQuery(range): ApiQuery<TimeRangeFilter>,
+    ApiQuery(rollup): ApiQuery<RollupFilter>,
+    ApiQuery(sort): ApiQuery<SortFilter>,
+) -> Result<Json<Page<SwapDataRunePoolInterval>>, AppError> {
+    let window = range.window()?;
+    let order_by = match &sort.sort_by {
+        None => window.order_by("start_time", "id"),
+        Some(key) if sort_keys().contains(key) => window.rank_by(key, "start_time", "id")?,
+        Some(key) => {
+            return Err(AppError::Validation(format!(
+                "cannot sort by {:?}, only by {}",
+                key,
+                sort_keys().join(", ")
+            )))
+        }
+    };
+    let source = match rollup.rollup()? {
+        None => "SELECT * FROM swap_data_rune_pool_interval WHERE pool = $6".to_string(),
+        Some(rollup) => format!(
+            "SELECT MIN(id) AS id, pool, {}, {}
+            FROM swap_data_rune_pool_interval
+            WHERE pool = $6
+            GROUP BY pool, {}",
+            rollup.times("start_time"),
+            swap_aggregates().join(", "),
+            rollup.bucket("start_time")
+        ),
+    };
+    let query = format!(
+        "SELECT * FROM ({}) s WHERE {} {}",
+        source,
+        window.conditions("start_time", "end_time", "id"),
+        order_by
+    );
+    let rows = window
+        .bind(sqlx::query_as::<_, SwapDataRunePoolInterval>(&query))
+        .bind(filter.pool.unwrap_or_else(|| AGGREGATE_POOL.to_string()))
+        .fetch_all(&pool)
+        .await?;
+
+    let page = window.page(rows, |row| (row.start_time, row.id));
+    match sort.sort_by {
+        None => Ok(Json(page)),
+        Some(_) => Ok(Json(Page {
+            data: page.data,
+            next_cursor: None,
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch_data_from_midgard::midgard_client::{HistoryParams, Interval, MidgardClient};
+    use crate::fetch_data_from_midgard::rate_limiter::RateLimiter;
+    use crate::fetch_data_from_midgard::retry::RetryPolicy;
+    use crate::ingestion_pipeline::dataset::Dataset;
+    use crate::ingestion_pipeline::ingest::{ingest_page, IntervalFilter};
+    use crate::ingestion_pipeline::validation::OnInconsistency;
+    use crate::mock_midgard::server::{spawn, MockOptions};
+    use crate::query_data_from_db::common::Order;
+    use std::str::FromStr;
+
+    // 2024-05-02 00:00 UTC
+    const NOW: i64 = 1_714_608_000;
+
+    fn sorted_by(key: &str) -> ApiQuery<SortFilter> {
+        ApiQuery(SortFilter {
+            sort_by: Some(key.to_string()),
+        })
+    }
+
+    #[sqlx::test(migrations = "./migrations")]
+    async fn ranks_intervals_by_fees_and_usd_volume(db: PgPool) {
+        let base_url = spawn(MockOptions {
+            now: Some(NOW),
+            ..MockOptions::default()
+        })
+        .await;
+        let midgard = MidgardClient::with_policy(
+            vec![base_url],
+            RetryPolicy::default(),
+            RateLimiter::new(1_000.0, 10),
+        );
+        let params = HistoryParams {
+            count: Some(48),
+            ..HistoryParams::new(Interval::Hour)
+        };
+        ingest_page(
+            &midgard,
+            &db,
+            Dataset::Swaps,
+            Some("BTC.BTC"),
+            &params,
+            IntervalFilter::default(),
+            OnInconsistency::Record,
         )
-    })?;
+        .await
+        .unwrap();
+        let btc = || {
+            ApiQuery(PoolFilter {
+                pool: Some("BTC.BTC".to_string()),
+            })
+        };
+        let largest_first = |count| {
+            ApiQuery(TimeRangeFilter {
+                count: Some(count),
+                order: Order::Desc,
+                ..TimeRangeFilter::default()
+            })
+        };
+        let by_hour = || ApiQuery(RollupFilter { interval: None });
 
-    Ok(Json(rows))
-}
\ No newline at end of file
+        let Json(all) = fetch_intervals(
+            State(db.clone()),
+            btc(),
+            largest_first(400),
+            by_hour(),
+            ApiQuery(SortFilter { sort_by: None }),
+        )
+        .await
+        .unwrap();
+        let most_fees = all.data.iter().filter_map(|i| i.total_fees.clone()).max();
+
+        for key in ["total_fees", "total_volume_usd", "synth_mint_fees"] {
+            let Json(top) = fetch_intervals(
+                State(db.clone()),
+                btc(),
+                largest_first(5),
+                by_hour(),
+                sorted_by(key),
+            )
+            .await
+            .unwrap();
+            assert_eq!(top.data.len(), 5);
+            assert_eq!(top.next_cursor, None);
+            let ranked: Vec<BigDecimal> = serde_json::to_value(&top.data)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|row| BigDecimal::from_str(row[key].as_str().unwrap()).unwrap())
+                .collect();
+            assert!(ranked.windows(2).all(|pair| pair[0] >= pair[1]), "{}", key);
+            if key == "total_fees" {
+                assert_eq!(Some(&ranked[0]), most_fees.as_ref());
+            }
+        }
+
+        // neither a column outside the list nor a cursor is taken
+        let unknown = fetch_intervals(
+            State(db.clone()),
+            btc(),
+            largest_first(5),
+            by_hour(),
+            sorted_by("pool"),
+        )
+        .await;
+        assert!(matches!(unknown, Err(AppError::Validation(_))));
+        let paged = fetch_intervals(
+            State(db),
+            btc(),
+            ApiQuery(TimeRangeFilter {
+                cursor: Some("1714521600:1".to_string()),
+                ..TimeRangeFilter::default()
+            }),
+            by_hour(),
+            sorted_by("total_fees"),
+        )
+        .await;
+        assert!(matches!(paged, Err(AppError::Validation(_))));
+    }
+}
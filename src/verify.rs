@@ -0,0 +1,96 @@
+use crate::error::AppError;
+use crate::ingestion_pipeline::dataset::Dataset;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+// One consistency check and how many rows (or migrations) violate it
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub problems: i64,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        self.problems == 0
+    }
+}
+
+async fn count(db: &PgPool, sql: &str) -> Result<i64, AppError> {
+    Ok(sqlx::query_scalar(sql).fetch_one(db).await?)
+}
+
+// Migrations in this build that are missing, failed or were edited after
+// being applied
+async fn unapplied_migrations(db: &PgPool) -> Result<i64, AppError> {
+    let applied: HashMap<i64, (bool, Vec<u8>)> = sqlx::query_as::<_, (i64, bool, Vec<u8>)>(
+        "SELECT version, success, checksum FROM _sqlx_migrations",
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|(version, success, checksum)| (version, (success, checksum)))
+    .collect();
+
+    let migrator = sqlx::migrate!("./migrations");
+    Ok(migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .filter(|m| match applied.get(&m.version) {
+            Some((success, checksum)) => !success || checksum.as_slice() != &*m.checksum,
+            None => true,
+        })
+        .count() as i64)
+}
+
+// Read-only checks over the stored data; none of them modify anything
+pub async fn verify(db: &PgPool) -> Result<Vec<Check>, AppError> {
+    let mut checks = vec![Check {
+        name: "migrations applied".to_string(),
+        problems: unapplied_migrations(db).await?,
+    }];
+
+    for dataset in Dataset::ALL {
+        let (table, start, end) = dataset.intervals_table();
+        let series = if dataset.is_per_pool() { "pool" } else { "1" };
+
+        checks.push(Check {
+            name: format!("{} intervals end after they start", dataset.as_str()),
+            problems: count(
+                db,
+                &format!("SELECT COUNT(*) FROM {} WHERE {} <= {}", table, end, start),
+            )
+            .await?,
+        });
+        checks.push(Check {
+            name: format!("{} intervals do not overlap", dataset.as_str()),
+            problems: count(
+                db,
+                &format!(
+                    "SELECT COUNT(*) FROM (
+                        SELECT {end}, LEAD({start}) OVER (PARTITION BY {series} ORDER BY {start}) AS next_start
+                        FROM {table}
+                    ) w WHERE next_start < {end}",
+                    table = table,
+                    start = start,
+                    end = end,
+                    series = series
+                ),
+            )
+            .await?,
+        });
+    }
+
+    // earlier, non-transactional writes could leave intervals without their pools
+    checks.push(Check {
+        name: "earnings intervals have a pool breakdown".to_string(),
+        problems: count(
+            db,
+            "SELECT COUNT(*) FROM earning_data_rune_pool_interval i
+             WHERE NOT EXISTS (SELECT 1 FROM earning_data_pool_data p WHERE p.interval_id = i.id)",
+        )
+        .await?,
+    });
+
+    Ok(checks)
+}